@@ -3,12 +3,13 @@ extern crate trex;
 extern crate ansi_term;
 
 use std::io::{self, Write};
+use std::process;
+use std::sync::mpsc::channel;
 use std::thread::{spawn, sleep};
-use std::sync::mpsc::{channel, Receiver};
 use std::time::{Duration, SystemTime};
 
 use trex::{System, EventQueue, EventEmitter, Simulation, World,
-           calc_millis, Entity};
+           calc_millis, Console, Entity};
 
 use ansi_term::Style;
 
@@ -37,55 +38,20 @@ pub struct Actor {
 impl Actor {
     pub fn new(room: Entity) -> Actor {
         Actor {
-            room: room,
+            room,
         }
     }
 }
 
 pub struct Input(pub String);
 
-pub struct InputSystem {
-    rx: Receiver<String>,
-}
-
-impl InputSystem {
-    pub fn new() -> InputSystem {
-        let (tx, rx) = channel();
-
-        spawn(move || {
-            let stdin = io::stdin();
-            loop {
-                let mut input = String::new();
-                match stdin.read_line(&mut input) {
-                    Ok(_) => if let Err(_) = tx.send(input) {
-                        break;
-                    },
-                    Err(_) => break,
-                };
-            }
-        });
-
-        InputSystem {
-            rx: rx,
-        }
-    }
-}
-
-impl System for InputSystem {
-    fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
-        while let Ok(input) = self.rx.try_recv() {
-            emitter.emit(Input(input));
-        }
-    }
-}
-
 pub struct Output(pub String);
 
 pub struct OutputSystem;
 
 impl System for OutputSystem {
     fn update(&mut self, _world: &mut World, queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {
-        for &Output(ref output) in queue.receive() {
+        for Output(output) in queue.receive() {
             print!("{}", output);
         }
 
@@ -96,8 +62,12 @@ impl System for OutputSystem {
 pub struct CommandSystem;
 
 impl System for CommandSystem {
+    fn init(&mut self, _world: &mut World, emitter: &mut EventEmitter) {
+        emitter.emit(Output(String::from("> ")));
+    }
+
     fn update(&mut self, world: &mut World, queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
-        for &Input(ref input) in queue.receive() {
+        for Input(input) in queue.receive() {
             match input.trim() {
                 "look" => {
                     let player = world.lookup("Player").unwrap();
@@ -110,8 +80,12 @@ impl System for CommandSystem {
                     emitter.emit(Output(output));
                 },
 
+                "dump" => {
+                    emitter.emit(Output(world.dump()));
+                },
+
                 "quit" => {
-                    emitter.emit(trex::Halt);
+                    emitter.emit(trex::Halt::new(0, "player quit"));
                     break;
                 },
 
@@ -129,47 +103,60 @@ components!(Actor, Room);
 events!(Input, Output);
 
 fn main() {
-    let world = {
-        let mut world = World::new();
-        world.register::<Actor>();
-        world.register::<Room>();
-
-        let player = world.create();
-        world.tag(player, "Player");
-        let entrance = world.create();
-
-        let actor = Actor::new(entrance);
-        let mut room = Room::new("Entrance", "You stand at the entrance to a dungeon.");
-        room.entities.push(player);
-
-        world.add(player, actor);
-        world.add(entrance, room);
-        world
-    };
-
-    let queue = {
-        let mut queue = EventQueue::new();
-        queue.register::<Input>();
-        queue.register::<Output>();
-        queue
-    };
-
-    let mut emitter = {
-        let mut emitter = EventEmitter::new();
-        emitter.register::<Input>();
-        emitter.register::<Output>();
-        emitter
-    };
-    emitter.emit(Output(String::from("> ")));
-
-    let mut simulation = Simulation::new(world, queue, emitter);
-    simulation.register(InputSystem::new());
-    simulation.register(CommandSystem);
-    simulation.register(OutputSystem);
+    let mut builder = Simulation::builder();
+    builder.add_component::<Actor>()
+        .add_component::<Room>()
+        .add_event::<Input>()
+        .add_event::<Output>()
+        .register(CommandSystem)
+        .register(OutputSystem);
+
+    let world = builder.world_mut();
+    world.register_debug::<Actor>();
+    world.register_debug::<Room>();
+    let player = world.create();
+    world.tag(player, "Player");
+    let entrance = world.create();
+
+    let actor = Actor::new(entrance);
+    let mut room = Room::new("Entrance", "You stand at the entrance to a dungeon.");
+    room.entities.push(player);
+
+    world.add(player, actor);
+    world.add(entrance, room);
+
+    let mut simulation = builder.build().unwrap();
+
+    // Lines that start with a slash are debug console commands, such as `/entities`.
+    let sender = simulation.sender();
+    let (commands, received) = channel();
+    spawn(move || {
+        let stdin = io::stdin();
+        loop {
+            let mut input = String::new();
+            let sent = match stdin.read_line(&mut input) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => match input.trim().strip_prefix('/') {
+                    Some(command) => commands.send(command.to_owned()).is_ok(),
+                    None => sender.send(Input(input)),
+                },
+            };
+            if !sent {
+                break;
+            }
+        }
+    });
+
+    let mut console = Console::new();
 
     let mut last = SystemTime::now();
 
     loop {
+        for command in received.try_iter() {
+            print!("{}> ", console.execute(&mut simulation, &command));
+            io::stdout().flush().unwrap();
+        }
+
         let now = SystemTime::now();
 
         if let Ok(dt) = now.duration_since(last) {
@@ -177,8 +164,8 @@ fn main() {
             simulation.update(dt_millis);
             last = now;
 
-            if simulation.halt() {
-                break;
+            if let Some(halt) = simulation.halt_status() {
+                process::exit(halt.code);
             }
         }
 
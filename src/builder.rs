@@ -0,0 +1,328 @@
+use std::any::{type_name, TypeId};
+use std::error;
+use std::fmt;
+
+use super::component::Component;
+use super::condition::RunCondition;
+use super::event::{Event, EventEmitter, EventPolicy, EventQueue, TargetedEvent};
+use super::param::{Access, ParamFunction};
+use super::simulation::Simulation;
+use super::state::State;
+use super::system::{Requirement, Requirements, System};
+use super::world::World;
+
+/// Bundles the components, events, resources and systems of a feature, such as physics or text
+/// parsing, so that they can be added to a `Simulation` in one call.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use trex::*;
+///
+/// pub struct Health(pub u32);
+///
+/// pub struct Damage { pub target: Entity, pub amount: u32 }
+///
+/// components!(Health);
+/// events!(Damage);
+///
+/// pub struct HealthPlugin;
+///
+/// impl Plugin for HealthPlugin {
+///     fn build(&self, builder: &mut SimulationBuilder) {
+///         builder.add_component::<Health>()
+///             .add_event::<Damage>()
+///             .register_fn(|world, queue, _emitter, _dt| {
+///                 for damage in queue.receive::<Damage>() {
+///                     let health = world.get_mut::<Health>(damage.target).unwrap();
+///                     health.0 = health.0.saturating_sub(damage.amount);
+///                 }
+///             });
+///     }
+/// }
+///
+/// fn main() {
+///     let mut builder = SimulationBuilder::new();
+///     builder.add_plugin(HealthPlugin);
+///     let mut simulation = builder.build().unwrap();
+///     simulation.update(16.0);
+/// }
+/// ```
+pub trait Plugin {
+    /// Register everything the plugin needs with the builder.
+    fn build(&self, builder: &mut SimulationBuilder);
+}
+
+/// Errors that can occur while building a `Simulation`.
+#[derive(Debug)]
+pub enum BuildError {
+    /// A `System` requires a component class, resource or event type that has not been
+    /// registered.
+    Unmet {
+        /// The name of the `System`.
+        system: String,
+        /// What kind of value is missing.
+        requirement: Requirement,
+        /// The name of the missing type.
+        name: String,
+    },
+    /// The same `Plugin` was added more than once.
+    DuplicatePlugin(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::Unmet { ref system, requirement, ref name } => {
+                let kind = match requirement {
+                    Requirement::Component => "component",
+                    Requirement::Resource => "resource",
+                    Requirement::Event => "event",
+                };
+                write!(f, "system `{}` requires {} `{}`, which has not been registered", system, kind, name)
+            },
+            BuildError::DuplicatePlugin(ref name) => write!(f, "plugin `{}` was added more than once", name),
+        }
+    }
+}
+
+impl error::Error for BuildError {}
+
+type Setup = Box<dyn FnOnce(&mut Simulation)>;
+
+/// Collects the components, events, resources, plugins and systems of a `Simulation`.
+///
+/// Components, events and resources are registered as soon as they are added. Systems and states
+/// are added to the `Simulation` in order when it is built, so every resource is available when
+/// the systems are initialized. Building fails if the requirements of a `System` are not met.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use trex::*;
+///
+/// pub struct Alarm;
+///
+/// events!(Alarm);
+///
+/// pub struct AlarmSystem;
+///
+/// impl System for AlarmSystem {
+///     fn requirements(&self, requirements: &mut Requirements) {
+///         requirements.event::<Alarm>();
+///     }
+///
+///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+///         emitter.emit(Alarm);
+///     }
+/// }
+///
+/// fn main() {
+///     let mut builder = Simulation::builder();
+///     builder.register(AlarmSystem);
+///     assert!(builder.build().is_err());
+///
+///     let mut builder = Simulation::builder();
+///     builder.add_event::<Alarm>().register(AlarmSystem);
+///     let mut simulation = builder.build().unwrap();
+///     simulation.update(16.0);
+/// }
+/// ```
+pub struct SimulationBuilder {
+    world: World,
+    queue: EventQueue,
+    emitter: EventEmitter,
+    setup: Vec<Setup>,
+    requirements: Vec<(&'static str, Requirements)>,
+    plugins: Vec<TypeId>,
+    errors: Vec<BuildError>,
+}
+
+impl SimulationBuilder {
+    /// Create an empty `SimulationBuilder`.
+    pub fn new() -> SimulationBuilder {
+        SimulationBuilder {
+            world: World::new(),
+            queue: EventQueue::new(),
+            emitter: EventEmitter::new(),
+            setup: Vec::new(),
+            requirements: Vec::new(),
+            plugins: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// The `World` of the `Simulation`, which can be used to create the initial entities.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Register a component class.
+    pub fn add_component<C: 'static + Component>(&mut self) -> &mut Self {
+        self.world.register::<C>();
+        self
+    }
+
+    /// Register an event type.
+    pub fn add_event<T: Event>(&mut self) -> &mut Self {
+        self.queue.register::<T>();
+        self.emitter.register::<T>();
+        self
+    }
+
+    /// Register an event type that is received according to the given policy.
+    pub fn add_event_with<T: Event>(&mut self, policy: EventPolicy<T>) -> &mut Self {
+        self.queue.register_with(policy);
+        self.emitter.register::<T>();
+        self
+    }
+
+    /// Register an event type that is addressed to entities.
+    pub fn add_targeted_event<T: TargetedEvent>(&mut self) -> &mut Self {
+        self.queue.register_targeted::<T>();
+        self.emitter.register_targeted::<T>();
+        self
+    }
+
+    /// Add a resource to the `World`.
+    pub fn add_resource<R: 'static>(&mut self, resource: R) -> &mut Self {
+        self.world.add_resource(resource);
+        self
+    }
+
+    /// Add a `State` resource that starts in the given state. See `Simulation::add_state`.
+    pub fn add_state<S: 'static + Clone + PartialEq>(&mut self, initial: S) -> &mut Self {
+        self.defer(move |simulation| simulation.add_state(initial))
+    }
+
+    /// Add everything in a `Plugin`. Adding the same `Plugin` twice is an error.
+    pub fn add_plugin<P: 'static + Plugin>(&mut self, plugin: P) -> &mut Self {
+        if self.plugins.contains(&TypeId::of::<P>()) {
+            self.errors.push(BuildError::DuplicatePlugin(type_name::<P>().to_owned()));
+        } else {
+            self.plugins.push(TypeId::of::<P>());
+            plugin.build(self);
+        }
+        self
+    }
+
+    /// Register a `System`. Its id can be looked up with `Simulation::system_id` once the
+    /// `Simulation` is built.
+    pub fn register<T: 'static + System>(&mut self, system: T) -> &mut Self {
+        self.require(&system, Requirements::new());
+        self.defer(move |simulation| { simulation.register(system); })
+    }
+
+    /// Register a `System` that keeps running while the `Simulation` is paused.
+    pub fn register_unpausable<T: 'static + System>(&mut self, system: T) -> &mut Self {
+        self.require(&system, Requirements::new());
+        self.defer(move |simulation| { simulation.register_unpausable(system); })
+    }
+
+    /// Register a `System` that only runs when the given condition is met.
+    pub fn register_if<C, T>(&mut self, condition: C, system: T) -> &mut Self
+        where C: 'static + RunCondition, T: 'static + System {
+        self.require(&system, Requirements::new());
+        self.defer(move |simulation| { simulation.register_if(condition, system); })
+    }
+
+    /// Register a closure as a `System`.
+    pub fn register_fn<F>(&mut self, func: F) -> &mut Self
+        where F: 'static + FnMut(&mut World, &EventQueue, &mut EventEmitter, f32) {
+        self.defer(move |simulation| { simulation.register_fn(func); })
+    }
+
+    /// Register a function as a `System`, passing it the parameters it asks for. The components
+    /// and resources in the parameters are required.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters borrow the same value mutably more than once, or both mutably and
+    /// immutably.
+    pub fn register_param_fn<P: 'static, F: 'static + ParamFunction<P>>(&mut self, func: F) -> &mut Self {
+        let mut access = Access::new();
+        F::access(&mut access);
+        self.requirements.push((type_name::<F>(), access.into_requirements()));
+        self.defer(move |simulation| { simulation.push_param_fn(func); })
+    }
+
+    /// Register a `System` that only runs while the `State` resource is in the given state.
+    pub fn register_in_state<S, T>(&mut self, state: S, system: T) -> &mut Self
+        where S: 'static + Clone + PartialEq, T: 'static + System {
+        self.require_state::<S, T>(&system);
+        self.defer(move |simulation| { simulation.register_in_state(state, system); })
+    }
+
+    /// Register a `System` that runs once, in the step in which the given state is entered.
+    pub fn register_on_enter<S, T>(&mut self, state: S, system: T) -> &mut Self
+        where S: 'static + Clone + PartialEq, T: 'static + System {
+        self.require_state::<S, T>(&system);
+        self.defer(move |simulation| { simulation.register_on_enter(state, system); })
+    }
+
+    /// Register a `System` that runs once, in the step in which the given state is left.
+    pub fn register_on_exit<S, T>(&mut self, state: S, system: T) -> &mut Self
+        where S: 'static + Clone + PartialEq, T: 'static + System {
+        self.require_state::<S, T>(&system);
+        self.defer(move |simulation| { simulation.register_on_exit(state, system); })
+    }
+
+    /// Call `handler` as soon as an event of the given type is emitted.
+    pub fn subscribe<T: Event, F: 'static + FnMut(&T)>(&mut self, handler: F) -> &mut Self {
+        self.emitter.subscribe(handler);
+        self
+    }
+
+    fn defer<F: 'static + FnOnce(&mut Simulation)>(&mut self, setup: F) -> &mut Self {
+        self.setup.push(Box::new(setup));
+        self
+    }
+
+    fn require<T: System>(&mut self, system: &T, mut requirements: Requirements) {
+        system.requirements(&mut requirements);
+        self.requirements.push((type_name::<T>(), requirements));
+    }
+
+    fn require_state<S: 'static, T: System>(&mut self, system: &T) {
+        let mut requirements = Requirements::new();
+        requirements.resource::<State<S>>();
+        self.require(system, requirements);
+    }
+
+    /// Create the `Simulation`, initializing every `System`, and check that the requirements of
+    /// every `System` are met.
+    pub fn build(self) -> Result<Simulation, BuildError> {
+        if let Some(err) = self.errors.into_iter().next() {
+            return Err(err);
+        }
+
+        let mut simulation = Simulation::from_parts(self.world, self.queue, self.emitter);
+        for setup in self.setup {
+            setup(&mut simulation);
+        }
+
+        for (system, requirements) in &self.requirements {
+            if let Some((requirement, name)) = simulation.unmet(requirements) {
+                return Err(BuildError::Unmet {
+                    system: (*system).to_owned(),
+                    requirement,
+                    name: name.to_owned(),
+                });
+            }
+        }
+
+        Ok(simulation)
+    }
+}
+
+impl Default for SimulationBuilder {
+    fn default() -> SimulationBuilder {
+        SimulationBuilder::new()
+    }
+}
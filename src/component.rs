@@ -1,11 +1,21 @@
+use std::any::type_name;
+use std::fmt;
+
 use vec_map::VecMap;
 
 use super::id::{Id, IdPool};
+use super::remap::EntityMap;
 use super::world::Entity;
-use super::family::{Family, FamilyMember, FamilyStore};
+use super::family::{Family, FamilyStore};
+
+/// Implemented by all component classes, usually with the `components!` macro.
+pub trait Component {
+    /// The unique identifier of the component class.
+    fn family() -> Family;
+}
 
 struct ComponentPool<T> {
-    data: Vec<T>,
+    data: Vec<Option<T>>,
     ids: IdPool,
 }
 
@@ -20,52 +30,88 @@ impl<T> ComponentPool<T> {
     fn add(&mut self, data: T) -> Id {
         let id = self.ids.reserve();
         if id < self.data.len() {
-            self.data[id] = data;
+            self.data[id] = Some(data);
         } else {
-            self.data.push(data);
+            self.data.push(Some(data));
         }
         id
     }
 
-    fn remove(&mut self, id: Id){
+    fn remove(&mut self, id: Id) -> Option<T> {
         self.ids.release(id);
+        self.data[id].take()
     }
 
     fn get(&self, id: Id) -> Option<&T> {
-        if self.ids.is_reserved(id) {
-            Some(&self.data[id])
-        } else {
-            None
-        }
+        self.data[id].as_ref()
     }
 
     fn get_mut(&mut self, id: Id) -> Option<&mut T> {
-        if self.ids.is_reserved(id) {
-            Some(&mut self.data[id])
-        } else {
-            None
-        }
+        self.data[id].as_mut()
+    }
+
+    // `as_mut_ptr` does not borrow the elements, so references to the other elements stay valid.
+    fn get_ptr(&mut self, id: Id) -> Option<*mut T> {
+        assert!(id < self.data.len());
+        unsafe { (*self.data.as_mut_ptr().add(id)).as_mut().map(|data| data as *mut T) }
     }
 }
 
 pub trait AnyComponentStore: FamilyStore {
+    fn empty(&self) -> Box<dyn AnyComponentStore>;
     fn remove(&mut self, entity: Entity);
+    fn clone_component(&mut self, from: Entity, to: Entity) -> bool;
+    fn copy_component(&self, entity: Entity, other: &mut dyn AnyComponentStore, other_entity: Entity) -> bool;
+    fn move_component(&mut self, entity: Entity, other: &mut dyn AnyComponentStore, other_entity: Entity) -> bool;
+    // Returns `false` if the component class has no mapper.
+    fn map_entities(&mut self, entity: Entity, map: &mut EntityMap) -> bool;
+    fn debug_component(&self, entity: Entity, f: &mut fmt::Formatter) -> fmt::Result;
+    #[cfg(feature = "serde")]
+    fn clear(&mut self);
 }
 
-pub struct InnerComponentStore<C: FamilyMember> {
+type Mapper<C> = fn(&mut C, &mut EntityMap);
+
+pub struct InnerComponentStore<C: Component> {
     map: VecMap<Id>,
     pool: ComponentPool<C>,
+    cloner: Option<fn(&C) -> C>,
+    mapper: Option<Mapper<C>>,
+    debugger: Option<fn(&C, &mut fmt::Formatter) -> fmt::Result>,
 }
 
-impl<C: FamilyMember> InnerComponentStore<C> {
+impl<C: Component> InnerComponentStore<C> {
     pub fn new() -> InnerComponentStore<C> {
         InnerComponentStore {
             map: VecMap::new(),
             pool: ComponentPool::new(),
+            cloner: None,
+            mapper: None,
+            debugger: None,
+        }
+    }
+
+    pub fn set_cloner(&mut self, cloner: fn(&C) -> C) {
+        self.cloner = Some(cloner);
+    }
+
+    pub fn set_mapper(&mut self, mapper: Mapper<C>) {
+        self.mapper = Some(mapper);
+    }
+
+    pub fn set_debugger(&mut self, debugger: fn(&C, &mut fmt::Formatter) -> fmt::Result) {
+        self.debugger = Some(debugger);
+    }
+
+    fn clone_of(&self, entity: Entity) -> Option<C> {
+        match (self.cloner, self.get(entity)) {
+            (Some(cloner), Some(component)) => Some(cloner(component)),
+            _ => None,
         }
     }
 
     pub fn add(&mut self, entity: Entity, data: C) {
+        self.take(entity);
         let id = self.pool.add(data);
         self.map.insert(entity, id);
     }
@@ -83,19 +129,104 @@ impl<C: FamilyMember> InnerComponentStore<C> {
             None => None,
         }
     }
+
+    pub(crate) fn get_ptr(&mut self, entity: Entity) -> Option<*mut C> {
+        match self.map.get(entity) {
+            Some(&id) => self.pool.get_ptr(id),
+            None => None,
+        }
+    }
 }
 
-impl<C: FamilyMember> FamilyStore for InnerComponentStore<C> {
+impl<C: Component> FamilyStore for InnerComponentStore<C> {
     fn family(&self) -> Family {
         C::family()
     }
 }
 
-impl<C: FamilyMember> AnyComponentStore for InnerComponentStore<C> {
+impl<C: Component> InnerComponentStore<C> {
+    pub fn take(&mut self, entity: Entity) -> Option<C> {
+        match self.map.remove(entity) {
+            Some(id) => self.pool.remove(id),
+            None => None,
+        }
+    }
+
+    fn downcast_mut(other: &mut dyn AnyComponentStore) -> &mut InnerComponentStore<C> {
+        assert_eq!(other.family(), C::family());
+        unsafe { &mut *(other as *mut dyn AnyComponentStore as *mut InnerComponentStore<C>) }
+    }
+}
+
+impl<C: 'static + Component> AnyComponentStore for InnerComponentStore<C> {
+    fn empty(&self) -> Box<dyn AnyComponentStore> {
+        let mut store = InnerComponentStore::<C>::new();
+        store.cloner = self.cloner;
+        store.mapper = self.mapper;
+        store.debugger = self.debugger;
+        Box::new(store)
+    }
+
     fn remove(&mut self, entity: Entity) {
-        if let Some(&id) = self.map.get(entity) {
-            self.pool.remove(id);
+        self.take(entity);
+    }
+
+    fn clone_component(&mut self, from: Entity, to: Entity) -> bool {
+        match self.clone_of(from) {
+            Some(component) => {
+                self.add(to, component);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn copy_component(&self, entity: Entity, other: &mut dyn AnyComponentStore, other_entity: Entity) -> bool {
+        let other = Self::downcast_mut(other);
+        match self.clone_of(entity) {
+            Some(component) => {
+                other.add(other_entity, component);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn move_component(&mut self, entity: Entity, other: &mut dyn AnyComponentStore, other_entity: Entity) -> bool {
+        let other = Self::downcast_mut(other);
+        match self.take(entity) {
+            Some(component) => {
+                other.add(other_entity, component);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn map_entities(&mut self, entity: Entity, map: &mut EntityMap) -> bool {
+        match self.mapper {
+            Some(mapper) => {
+                if let Some(component) = self.get_mut(entity) {
+                    mapper(component, map);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Components that cannot be formatted are shown by the name of their type.
+    fn debug_component(&self, entity: Entity, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.debugger, self.get(entity)) {
+            (Some(debugger), Some(component)) => debugger(component, f),
+            _ => f.write_str(type_name::<C>()),
         }
     }
+
+    #[cfg(feature = "serde")]
+    fn clear(&mut self) {
+        self.map.clear();
+        self.pool = ComponentPool::new();
+    }
 }
 
@@ -0,0 +1,156 @@
+use std::marker::PhantomData;
+
+use super::event::{Event, EventQueue};
+use super::world::World;
+
+/// Decides whether a `System` runs during a frame. Registered with `Simulation::register_if`.
+///
+/// Every condition of a `System` is checked in every frame, even if an earlier one fails, so that
+/// conditions that keep track of time stay up to date. Closures that take the same arguments as
+/// `check` are conditions too.
+pub trait RunCondition {
+    /// Returns `true` if the `System` should run during this frame, otherwise `false`. `queue`
+    /// contains the events that the `System` has not yet received.
+    fn check(&mut self, world: &World, queue: &EventQueue, dt: f32) -> bool;
+}
+
+impl<F: FnMut(&World, &EventQueue, f32) -> bool> RunCondition for F {
+    fn check(&mut self, world: &World, queue: &EventQueue, dt: f32) -> bool {
+        self(world, queue, dt)
+    }
+}
+
+/// Runs a `System` once each time the given number of milliseconds has passed.
+pub struct Every {
+    interval: f32,
+    elapsed: f32,
+}
+
+impl Every {
+    /// Create a condition that is met once every `interval` milliseconds.
+    pub fn millis(interval: f32) -> Every {
+        Every {
+            interval,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl RunCondition for Every {
+    fn check(&mut self, _world: &World, _queue: &EventQueue, dt: f32) -> bool {
+        self.elapsed += dt;
+        if self.elapsed >= self.interval {
+            self.elapsed -= self.interval;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Runs a `System` only when it has events of the given type to receive.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use trex::*;
+///
+/// pub struct Ping;
+///
+/// events!(Ping);
+///
+/// fn main() {
+///     let world = World::new();
+///     let mut emitter = EventEmitter::new();
+///     emitter.register::<Ping>();
+///     let mut queue = EventQueue::new();
+///     queue.merge(&mut emitter);
+///
+///     let mut condition = OnEvent::<Ping>::new();
+///     assert!(!condition.check(&world, &queue, 16.0));
+///
+///     emitter.emit(Ping);
+///     queue.merge(&mut emitter);
+///     assert!(condition.check(&world, &queue, 16.0));
+/// }
+/// ```
+pub struct OnEvent<T> {
+    _event: PhantomData<T>,
+}
+
+impl<T: Event> OnEvent<T> {
+    /// Create a condition that is met when there are events of type `T` to receive.
+    pub fn new() -> OnEvent<T> {
+        OnEvent {
+            _event: PhantomData,
+        }
+    }
+}
+
+impl<T: Event> Default for OnEvent<T> {
+    fn default() -> OnEvent<T> {
+        OnEvent::new()
+    }
+}
+
+impl<T: Event> RunCondition for OnEvent<T> {
+    fn check(&mut self, _world: &World, queue: &EventQueue, _dt: f32) -> bool {
+        queue.receive::<T>().next().is_some()
+    }
+}
+
+/// Runs a `System` only when a resource has been added or borrowed mutably since the last check.
+///
+/// # Examples
+///
+/// ```
+/// use trex::*;
+///
+/// pub struct Score(u32);
+///
+/// let mut world = World::new();
+/// let queue = EventQueue::new();
+/// let mut condition = ResourceChanged::<Score>::new();
+/// assert!(!condition.check(&world, &queue, 16.0));
+///
+/// world.add_resource(Score(0));
+/// assert!(condition.check(&world, &queue, 16.0));
+/// assert!(!condition.check(&world, &queue, 16.0));
+///
+/// world.resource_mut::<Score>().unwrap().0 += 1;
+/// assert!(condition.check(&world, &queue, 16.0));
+/// assert!(!condition.check(&world, &queue, 16.0));
+/// ```
+pub struct ResourceChanged<R> {
+    version: Option<usize>,
+    _resource: PhantomData<R>,
+}
+
+impl<R: 'static> ResourceChanged<R> {
+    /// Create a condition that is met when the resource of type `R` changes. A resource that
+    /// exists when the condition is first checked counts as changed.
+    pub fn new() -> ResourceChanged<R> {
+        ResourceChanged {
+            version: None,
+            _resource: PhantomData,
+        }
+    }
+}
+
+impl<R: 'static> Default for ResourceChanged<R> {
+    fn default() -> ResourceChanged<R> {
+        ResourceChanged::new()
+    }
+}
+
+impl<R: 'static> RunCondition for ResourceChanged<R> {
+    fn check(&mut self, world: &World, _queue: &EventQueue, _dt: f32) -> bool {
+        let version = world.resource_version::<R>();
+        let changed = version.is_some() && version != self.version;
+        self.version = version;
+        changed
+    }
+}
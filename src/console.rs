@@ -0,0 +1,289 @@
+#[cfg(feature = "serde")]
+use serde_json::{self, Value};
+
+use super::simulation::Simulation;
+use super::system::SystemId;
+#[cfg(feature = "serde")]
+use super::serialize::TypeRegistry;
+use super::world::{ComponentFilter, Entity, World};
+
+const HELP: &str = "\
+help                               list the commands
+entities                           list every entity with its tag and components
+spawn [tag]                        create an entity
+destroy <entity>                   destroy an entity
+inspect <entity> [component]       show the registered components of an entity as JSON
+set <entity> <component> <json>    attach a component, replacing any of the same class
+set <entity> <component.field> <json>
+                                   change a field of a component
+systems                            list the systems
+toggle <system>                    enable or disable a system at the start of the next step
+pause                              pause the simulation
+resume                             resume the simulation
+step [frames] [dt]                 perform steps of `dt` milliseconds while paused
+";
+
+// The length of a step performed by the `step` command, unless one is given.
+const STEP_MILLIS: f32 = 16.0;
+
+/// Runs text commands that inspect and change a `Simulation` between updates, such as lines read
+/// from stdin by the owner of the `Simulation`. Entities can be given by id or by tag, and
+/// systems by the id returned when they were registered. Run `help` for a list of commands.
+///
+/// Inspecting and changing components requires the `serde` feature and a `TypeRegistry`, given
+/// to `Console::with_registry`.
+///
+/// # Examples
+///
+/// ```
+/// use trex::*;
+///
+/// let mut simulation = Simulation::builder().build().unwrap();
+/// let id = simulation.register_fn(|_world, _queue, _emitter, _dt| {});
+/// let mut console = Console::new();
+///
+/// assert_eq!(console.execute(&mut simulation, "spawn Player"), "created entity 0\n");
+/// assert_eq!(simulation.world().lookup("Player"), Some(0));
+///
+/// console.execute(&mut simulation, "pause");
+/// console.execute(&mut simulation, "step 3");
+/// assert!(simulation.is_paused());
+/// assert_eq!(simulation.world().resource::<Time>().unwrap().elapsed, 48.0);
+///
+/// assert_eq!(console.execute(&mut simulation, "toggle 0"), "system 0 will be disabled\n");
+/// simulation.update(16.0);
+/// assert!(!simulation.is_enabled(id));
+///
+/// // Toggling again before the next update undoes the pending change.
+/// assert_eq!(console.execute(&mut simulation, "toggle 0"), "system 0 will be enabled\n");
+/// assert_eq!(console.execute(&mut simulation, "toggle 0"), "system 0 will be disabled\n");
+/// assert_eq!(console.execute(&mut simulation, "toggle 0"), "system 0 will be enabled\n");
+/// simulation.update(16.0);
+/// assert!(simulation.is_enabled(id));
+///
+/// assert!(console.execute(&mut simulation, "destroy Enemy").starts_with("error:"));
+/// assert_eq!(console.execute(&mut simulation, "destroy 5"), "error: no entity `5`\n");
+/// ```
+pub struct Console {
+    #[cfg(feature = "serde")]
+    registry: Option<TypeRegistry>,
+}
+
+impl Console {
+    /// Create a `Console` that cannot inspect or change components.
+    pub fn new() -> Console {
+        Console {
+            #[cfg(feature = "serde")]
+            registry: None,
+        }
+    }
+
+    /// Create a `Console` that can inspect and change the components in the registry. Fields are
+    /// named by their path from the component, such as `Position.x`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Health { pub current: u32, pub max: u32 }
+    ///
+    /// components!(Health);
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register::<Health>("Health");
+    ///
+    ///     let mut builder = Simulation::builder();
+    ///     let world = builder.add_component::<Health>().world_mut();
+    ///     let player = world.create();
+    ///     world.tag(player, "Player");
+    ///     world.add(player, Health { current: 10, max: 100 });
+    ///     let mut simulation = builder.build().unwrap();
+    ///
+    ///     let mut console = Console::with_registry(registry);
+    ///     let output = console.execute(&mut simulation, "set Player Health.current 100");
+    ///     assert_eq!(output, "Health = {\"current\":100,\"max\":100}\n");
+    ///     assert_eq!(simulation.world().get::<Health>(player).unwrap().current, 100);
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn with_registry(registry: TypeRegistry) -> Console {
+        Console {
+            registry: Some(registry),
+        }
+    }
+
+    /// Run a single command and return its output. Errors are returned as output that starts
+    /// with `error:`.
+    pub fn execute(&mut self, simulation: &mut Simulation, line: &str) -> String {
+        let args = line.split_whitespace().collect::<Vec<&str>>();
+        let result = match args.split_first() {
+            Some((command, args)) => self.run(simulation, command, args),
+            None => Ok(String::new()),
+        };
+
+        match result {
+            Ok(output) => output,
+            Err(err) => format!("error: {}\n", err),
+        }
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, command: &str, args: &[&str]) -> Result<String, String> {
+        match command {
+            "help" => Ok(HELP.to_owned()),
+            "entities" => Ok(simulation.world().dump()),
+            "spawn" => {
+                let world = simulation.world_mut();
+                let entity = world.create();
+                if let Some(tag) = args.first() {
+                    world.tag(entity, tag);
+                }
+                Ok(format!("created entity {}\n", entity))
+            },
+            "destroy" => {
+                let entity = find_entity(simulation.world(), arg(args, 0, "entity")?)?;
+                simulation.world_mut().destroy(entity);
+                Ok(format!("destroyed entity {}\n", entity))
+            },
+            "systems" => {
+                let mut output = String::new();
+                for (id, name) in simulation.systems() {
+                    let state = if simulation.is_enabled(id) { "enabled" } else { "disabled" };
+                    output.push_str(&format!("{:>4} {:<8} {}\n", id.0, state, name));
+                }
+                Ok(output)
+            },
+            "toggle" => {
+                let id = find_system(simulation, arg(args, 0, "system")?)?;
+                let enabled = !simulation.will_be_enabled(id);
+                simulation.set_enabled(id, enabled);
+                let state = if enabled { "enabled" } else { "disabled" };
+                Ok(format!("system {} will be {}\n", id.0, state))
+            },
+            "pause" => {
+                simulation.pause();
+                Ok(String::new())
+            },
+            "resume" => {
+                simulation.resume();
+                Ok(String::new())
+            },
+            "step" => {
+                if !simulation.is_paused() {
+                    return Err(String::from("the simulation is not paused"));
+                }
+                let frames = parse_or(args.first(), 1)?;
+                let dt = parse_or(args.get(1), STEP_MILLIS)?;
+
+                // Every system runs during the steps, and the `Simulation` is paused again
+                // afterwards.
+                simulation.resume();
+                for _ in 0..frames {
+                    simulation.update(dt);
+                }
+                simulation.pause();
+                Ok(String::new())
+            },
+            #[cfg(feature = "serde")]
+            "inspect" => self.inspect(simulation, args),
+            #[cfg(feature = "serde")]
+            "set" => self.set(simulation, args),
+            _ => Err(format!("unknown command `{}`, try `help`", command)),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn registry(&self) -> Result<&TypeRegistry, String> {
+        self.registry.as_ref()
+            .ok_or_else(|| String::from("components can only be inspected with a `TypeRegistry`"))
+    }
+
+    #[cfg(feature = "serde")]
+    fn inspect(&self, simulation: &Simulation, args: &[&str]) -> Result<String, String> {
+        let registry = self.registry()?;
+        let world = simulation.world();
+        let entity = find_entity(world, arg(args, 0, "entity")?)?;
+
+        let value = match args.get(1) {
+            Some(name) => registry.save_component(world, entity, name).map_err(|err| err.to_string())?
+                .ok_or_else(|| format!("entity {} does not have `{}`", entity, name))?,
+            None => {
+                let saved = registry.save_entity(world, entity).map_err(|err| err.to_string())?;
+                serde_json::to_value(saved.components).map_err(|err| err.to_string())?
+            },
+        };
+        let json = serde_json::to_string_pretty(&value).map_err(|err| err.to_string())?;
+        Ok(format!("{}\n", json))
+    }
+
+    #[cfg(feature = "serde")]
+    fn set(&self, simulation: &mut Simulation, args: &[&str]) -> Result<String, String> {
+        let registry = self.registry()?;
+        let world = simulation.world_mut();
+        let entity = find_entity(world, arg(args, 0, "entity")?)?;
+        let target = arg(args, 1, "component")?;
+        if args.len() < 3 {
+            return Err(String::from("missing value"));
+        }
+        let value = serde_json::from_str::<Value>(&args[2..].join(" ")).map_err(|err| err.to_string())?;
+
+        let (name, component) = match target.find('.') {
+            Some(index) => {
+                let name = &target[..index];
+                let mut component = registry.save_component(world, entity, name).map_err(|err| err.to_string())?
+                    .ok_or_else(|| format!("entity {} does not have `{}`", entity, name))?;
+                let pointer = format!("/{}", target[index + 1..].replace('.', "/"));
+                match component.pointer_mut(&pointer) {
+                    Some(field) => *field = value,
+                    None => return Err(format!("`{}` has no field `{}`", name, &target[index + 1..])),
+                }
+                (name, component)
+            },
+            None => (target, value),
+        };
+
+        registry.load_component(world, entity, name, component).map_err(|err| err.to_string())?;
+        let saved = registry.save_component(world, entity, name).map_err(|err| err.to_string())?;
+        Ok(format!("{} = {}\n", name, saved.unwrap_or(Value::Null)))
+    }
+}
+
+impl Default for Console {
+    fn default() -> Console {
+        Console::new()
+    }
+}
+
+fn arg<'a>(args: &[&'a str], index: usize, name: &str) -> Result<&'a str, String> {
+    args.get(index).cloned().ok_or_else(|| format!("missing {}", name))
+}
+
+fn parse_or<T: ::std::str::FromStr>(arg: Option<&&str>, default: T) -> Result<T, String> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("`{}` is not a number", arg)),
+        None => Ok(default),
+    }
+}
+
+// Entities are looked up by id first, and then by tag. Ids that were never created do not match
+// any filter, rather than panicking as `World::exists` does.
+fn find_entity(world: &World, name: &str) -> Result<Entity, String> {
+    match name.parse::<Entity>() {
+        Ok(entity) if world.matches(entity, &ComponentFilter::new()) => Ok(entity),
+        _ => world.lookup(name).ok_or_else(|| format!("no entity `{}`", name)),
+    }
+}
+
+fn find_system(simulation: &Simulation, name: &str) -> Result<SystemId, String> {
+    simulation.systems().into_iter()
+        .map(|(id, _)| id)
+        .find(|id| id.0.to_string() == name)
+        .ok_or_else(|| format!("no system `{}`", name))
+}
@@ -1,59 +1,333 @@
+use std::any::{type_name, TypeId};
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::slice::Iter as SliceIter;
-use std::mem::transmute;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::vec::IntoIter as VecIntoIter;
 
 use vec_map::VecMap;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 
-use super::family::{Family, FamilyMember, FamilyStore};
+use super::world::{ComponentFilter, Entity, World};
 
-trait AnyEventQueue: FamilyStore {
+/// Implemented by all event types, usually with the `events!` macro.
+pub trait Event: 'static {}
+
+/// Implemented by events that are addressed to a particular `Entity`.
+pub trait TargetedEvent: Event {
+    /// The `Entity` that the event is addressed to.
+    fn target(&self) -> Entity;
+}
+
+trait EventStore {
+    fn event_type(&self) -> TypeId;
+}
+
+trait AnyEventQueue: EventStore {
     fn flush(&mut self);
-    fn merge(&mut self, any_emitter: &mut Box<AnyEventEmitter>);
+    fn clear(&mut self);
+    fn advance(&mut self, reader: Reader);
+    fn merge(&mut self, any_emitter: &mut Box<dyn AnyEventEmitter>);
+}
+
+trait AnyEventEmitter: EventStore {
+    fn new_queue(&self) -> Box<dyn AnyEventQueue>;
+    #[cfg(feature = "serde")]
+    fn clear(&mut self);
+}
+
+/// Controls how the events of a type are received. Events are filtered first, then duplicates are
+/// coalesced, and finally the remaining events are ordered by priority.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use trex::*;
+///
+/// pub struct Resize { pub width: u32, pub height: u32 }
+///
+/// pub struct Message { pub urgent: bool, pub text: &'static str }
+///
+/// events!(Resize, Message);
+///
+/// fn main() {
+///     let mut queue = EventQueue::new();
+///     queue.register_with::<Resize>(EventPolicy::new().coalesce(|_, _| true));
+///     queue.register_with::<Message>(EventPolicy::new()
+///         .filter(|message: &Message| !message.text.is_empty())
+///         .priority(|message: &Message| if message.urgent { 1 } else { 0 }));
+///
+///     let mut emitter = EventEmitter::new();
+///     emitter.register::<Resize>();
+///     emitter.register::<Message>();
+///     emitter.emit(Resize { width: 640, height: 480 });
+///     emitter.emit(Resize { width: 800, height: 600 });
+///     emitter.emit(Message { urgent: false, text: "hello" });
+///     emitter.emit(Message { urgent: false, text: "" });
+///     emitter.emit(Message { urgent: true, text: "fire" });
+///     queue.merge(&mut emitter);
+///
+///     let sizes = queue.receive::<Resize>().map(|r| (r.width, r.height)).collect::<Vec<_>>();
+///     assert_eq!(sizes, vec![(800, 600)]);
+///     let texts = queue.receive::<Message>().map(|m| m.text).collect::<Vec<_>>();
+///     assert_eq!(texts, vec!["fire", "hello"]);
+/// }
+/// ```
+pub struct EventPolicy<T> {
+    filter: Option<Predicate<T>>,
+    coalesce: Option<Duplicate<T>>,
+    priority: Option<Priority<T>>,
 }
 
-trait AnyEventEmitter: FamilyStore { }
+type Predicate<T> = Box<dyn Fn(&T) -> bool>;
+type Duplicate<T> = Box<dyn Fn(&T, &T) -> bool>;
+type Priority<T> = Box<dyn Fn(&T) -> i32>;
+
+impl<T> EventPolicy<T> {
+    /// Create a policy that receives every event in the order it was emitted.
+    pub fn new() -> EventPolicy<T> {
+        EventPolicy {
+            filter: None,
+            coalesce: None,
+            priority: None,
+        }
+    }
+
+    /// Only receive the events for which `predicate` returns `true`.
+    pub fn filter<F: 'static + Fn(&T) -> bool>(mut self, predicate: F) -> Self {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+
+    /// Only receive the last of the events that `duplicate` considers to be the same.
+    pub fn coalesce<F: 'static + Fn(&T, &T) -> bool>(mut self, duplicate: F) -> Self {
+        self.coalesce = Some(Box::new(duplicate));
+        self
+    }
+
+    /// Receive events with a higher priority first. Events with the same priority are received in
+    /// the order they were emitted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Alarm(pub i32);
+    ///
+    /// events!(Alarm);
+    ///
+    /// fn main() {
+    ///     let mut queue = EventQueue::new();
+    ///     queue.register_with::<Alarm>(EventPolicy::new().priority(|alarm: &Alarm| alarm.0));
+    ///
+    ///     let mut emitter = EventEmitter::new();
+    ///     emitter.register::<Alarm>();
+    ///     emitter.emit(Alarm(i32::MIN));
+    ///     emitter.emit(Alarm(0));
+    ///     emitter.emit(Alarm(i32::MAX));
+    ///     queue.merge(&mut emitter);
+    ///
+    ///     let alarms = queue.receive::<Alarm>().map(|alarm| alarm.0).collect::<Vec<_>>();
+    ///     assert_eq!(alarms, vec![i32::MAX, 0, i32::MIN]);
+    /// }
+    /// ```
+    pub fn priority<F: 'static + Fn(&T) -> i32>(mut self, priority: F) -> Self {
+        self.priority = Some(Box::new(priority));
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.filter.is_none() && self.coalesce.is_none() && self.priority.is_none()
+    }
+
+    fn apply<'a>(&self, events: Vec<&'a T>) -> Vec<&'a T> {
+        let mut events = match self.filter {
+            Some(ref filter) => events.into_iter().filter(|&event| filter(event)).collect(),
+            None => events,
+        };
+
+        if let Some(ref duplicate) = self.coalesce {
+            let mut coalesced = Vec::with_capacity(events.len());
+            for (index, event) in events.iter().enumerate() {
+                if !events[index + 1..].iter().any(|later| duplicate(event, later)) {
+                    coalesced.push(*event);
+                }
+            }
+            events = coalesced;
+        }
+
+        if let Some(ref priority) = self.priority {
+            events.sort_by_key(|&event| Reverse(priority(event)));
+        }
+
+        events
+    }
+}
+
+impl<T> Default for EventPolicy<T> {
+    fn default() -> EventPolicy<T> {
+        EventPolicy::new()
+    }
+}
+
+/// Identifies a reader of an `EventQueue`, such as a `System`.
+pub type Reader = usize;
 
 struct InnerEventQueue<T> {
     events: Vec<T>,
+    // Sequence number of the first retained event.
+    first: usize,
+    // Sequence number of the first event emitted during the current frame.
+    frame_start: usize,
+    // Sequence number of the next event that each reader will receive.
+    cursors: VecMap<usize>,
+    router: Option<fn(&T) -> Entity>,
+    // Sequence numbers of the retained events addressed to each entity.
+    routes: HashMap<Entity, Vec<usize>>,
+    policy: EventPolicy<T>,
 }
 
 impl<T> InnerEventQueue<T> {
-    fn new() -> InnerEventQueue<T> {
+    fn new(router: Option<fn(&T) -> Entity>) -> InnerEventQueue<T> {
         InnerEventQueue {
             events: Vec::new(),
+            first: 0,
+            frame_start: 0,
+            cursors: VecMap::new(),
+            router,
+            routes: HashMap::new(),
+            policy: EventPolicy::new(),
         }
     }
 
-    fn receive(&self) -> Iter<T> {
-        Iter::new(self.events.iter())
+    fn end(&self) -> usize {
+        self.first + self.events.len()
+    }
+
+    fn start(&self, reader: Reader) -> usize {
+        let cursor = self.cursors.get(reader).cloned().unwrap_or(self.first);
+        cursor.max(self.first)
     }
+
+    fn receive(&self, reader: Reader) -> Iter<'_, T> {
+        let start = self.start(reader) - self.first;
+        if self.policy.is_empty() {
+            Iter::new(self.events[start..].iter())
+        } else {
+            Iter::collected(self.policy.apply(self.events[start..].iter().collect()))
+        }
+    }
+
+    fn receive_routed<P: Fn(Entity) -> bool>(&self, reader: Reader, predicate: P) -> Iter<'_, T> {
+        let start = self.start(reader);
+        let mut sequence = self.routes.iter()
+            .filter(|&(&entity, _)| predicate(entity))
+            .flat_map(|(_, sequence)| sequence.iter().cloned())
+            .filter(|&number| number >= start)
+            .collect::<Vec<usize>>();
+        sequence.sort();
+
+        let events = sequence.into_iter()
+            .map(|number| &self.events[number - self.first])
+            .collect::<Vec<&T>>();
+        Iter::collected(self.policy.apply(events))
+    }
+
+    fn route(&mut self, number: usize) {
+        if let Some(router) = self.router {
+            let target = router(&self.events[number - self.first]);
+            self.routes.entry(target).or_default().push(number);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn reroute(&mut self) {
+        self.routes.clear();
+        for number in self.first..self.end() {
+            self.route(number);
+        }
+    }
+}
+
+/// The position of each reader in the queue of an event type.
+#[cfg(feature = "serde")]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QueueState {
+    first: usize,
+    frame_start: usize,
+    cursors: Vec<(Reader, usize)>,
 }
 
-impl<T: FamilyMember> FamilyStore for InnerEventQueue<T> {
-    fn family(&self) -> Family {
-        T::family()
+impl<T: Event> EventStore for InnerEventQueue<T> {
+    fn event_type(&self) -> TypeId {
+        TypeId::of::<T>()
     }
 }
 
-impl<T: FamilyMember> AnyEventQueue for InnerEventQueue<T> {
+impl<T: Event> AnyEventQueue for InnerEventQueue<T> {
     fn flush(&mut self) {
+        let expired = self.frame_start - self.first;
+        self.events.drain(..expired);
+        self.first = self.frame_start;
+        self.frame_start = self.end();
+
+        let first = self.first;
+        self.routes.retain(|_, sequence| {
+            sequence.retain(|&number| number >= first);
+            !sequence.is_empty()
+        });
+    }
+
+    fn clear(&mut self) {
+        self.first = self.end();
+        self.frame_start = self.first;
         self.events.clear();
+        self.routes.clear();
+    }
+
+    fn advance(&mut self, reader: Reader) {
+        let end = self.end();
+        self.cursors.insert(reader, end);
     }
 
-    fn merge(&mut self, any_emitter: &mut Box<AnyEventEmitter>) {
-        assert_eq!(self.family(), any_emitter.family());
-        let emitter: &mut Box<InnerEventEmitter<T>> = unsafe { transmute(any_emitter) };
+    fn merge(&mut self, any_emitter: &mut Box<dyn AnyEventEmitter>) {
+        assert_eq!(self.event_type(), any_emitter.event_type());
+        let emitter = unsafe { &mut *(&mut **any_emitter as *mut dyn AnyEventEmitter as *mut InnerEventEmitter<T>) };
+        let start = self.end();
         self.events.append(&mut emitter.events);
+        for number in start..self.end() {
+            self.route(number);
+        }
     }
 }
 
+enum InnerIter<'a, T: 'a> {
+    Slice(SliceIter<'a, T>),
+    Collected(VecIntoIter<&'a T>),
+}
+
 pub struct Iter<'a, T: 'a> {
-    iter: SliceIter<'a, T>,
+    iter: InnerIter<'a, T>,
 }
 
 impl<'a, T: 'a> Iter<'a, T> {
     fn new(iter: SliceIter<'a, T>) -> Iter<'a, T> {
         Iter {
-            iter: iter,
+            iter: InnerIter::Slice(iter),
+        }
+    }
+
+    fn collected(events: Vec<&'a T>) -> Iter<'a, T> {
+        Iter {
+            iter: InnerIter::Collected(events.into_iter()),
         }
     }
 }
@@ -62,101 +336,606 @@ impl<'a, T: 'a> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<&'a T> {
-        self.iter.next()
+        match self.iter {
+            InnerIter::Slice(ref mut iter) => iter.next(),
+            InnerIter::Collected(ref mut iter) => iter.next(),
+        }
     }
 }
 
+type Handler<T> = Box<dyn FnMut(&T)>;
+
 struct InnerEventEmitter<T> {
     events: Vec<T>,
+    handlers: Vec<Handler<T>>,
+    router: Option<fn(&T) -> Entity>,
 }
 
 impl<T> InnerEventEmitter<T> {
-    fn new() -> InnerEventEmitter<T> {
+    fn new(router: Option<fn(&T) -> Entity>) -> InnerEventEmitter<T> {
         InnerEventEmitter {
             events: Vec::new(),
+            handlers: Vec::new(),
+            router,
         }
     }
 
     fn emit(&mut self, event: T) {
+        for handler in &mut self.handlers {
+            handler(&event);
+        }
         self.events.push(event);
     }
 }
 
-impl<T: FamilyMember> FamilyStore for InnerEventEmitter<T> {
-    fn family(&self) -> Family {
-        T::family()
+impl<T: Event> EventStore for InnerEventEmitter<T> {
+    fn event_type(&self) -> TypeId {
+        TypeId::of::<T>()
     }
 }
 
-impl<T: FamilyMember> AnyEventEmitter for InnerEventEmitter<T> {
+impl<T: Event> AnyEventEmitter for InnerEventEmitter<T> {
+    fn new_queue(&self) -> Box<dyn AnyEventQueue> {
+        Box::new(InnerEventQueue::<T>::new(self.router))
+    }
 
+    #[cfg(feature = "serde")]
+    fn clear(&mut self) {
+        self.events.clear();
+    }
 }
 
 /// Used to receive registered events.
+///
+/// Events are kept for two simulation steps and each reader keeps track of the events it has
+/// received, so every `System` receives each event exactly once, regardless of whether it was
+/// emitted by a `System` that runs before or after it.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use std::rc::Rc;
+/// use std::cell::Cell;
+///
+/// use trex::*;
+///
+/// pub struct Ping;
+///
+/// events!(Ping);
+///
+/// pub struct CountSystem(Rc<Cell<usize>>);
+///
+/// impl System for CountSystem {
+///     fn update(&mut self, _world: &mut World, queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {
+///         let count = self.0.get() + queue.receive::<Ping>().count();
+///         self.0.set(count);
+///     }
+/// }
+///
+/// pub struct PingSystem;
+///
+/// impl System for PingSystem {
+///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+///         emitter.emit(Ping);
+///     }
+/// }
+///
+/// fn main() {
+///     let count = Rc::new(Cell::new(0));
+///     let mut builder = Simulation::builder();
+///     builder.add_event::<Ping>()
+///         .register(CountSystem(count.clone()))
+///         .register(PingSystem);
+///     let mut simulation = builder.build().unwrap();
+///
+///     simulation.update(16.0);
+///     assert_eq!(count.get(), 0);
+///     simulation.update(16.0);
+///     simulation.update(16.0);
+///     assert_eq!(count.get(), 2);
+/// }
+/// ```
 pub struct EventQueue {
-    queues: VecMap<Box<AnyEventQueue>>,
+    queues: HashMap<TypeId, Box<dyn AnyEventQueue>>,
+    reader: Reader,
+    // Events are only kept for a second step once a `Simulation` has started tracking readers.
+    tracks_readers: bool,
 }
 
 impl EventQueue {
     /// Create a new, empty `EventQueue`.
     pub fn new() -> EventQueue {
         EventQueue {
-            queues: VecMap::new(),
+            queues: HashMap::new(),
+            reader: 0,
+            tracks_readers: false,
         }
     }
 
     /// Register a new event type.
-    pub fn register<T: 'static + FamilyMember>(&mut self) {
-        self.queues.insert(T::family(), Box::new(InnerEventQueue::<T>::new()));
+    pub fn register<T: Event>(&mut self) {
+        self.queues.insert(TypeId::of::<T>(), Box::new(InnerEventQueue::<T>::new(None)));
+    }
+
+    /// Register a new event type that is received according to the given policy.
+    pub fn register_with<T: Event>(&mut self, policy: EventPolicy<T>) {
+        self.register::<T>();
+        self.set_policy(policy);
     }
 
-    /// Iterate over all events of the given type that have been emitted during
-    /// the current simulation step.
-    pub fn receive<T: FamilyMember>(&self) -> Iter<T> {
-        let any_queue = self.queues.get(T::family()).unwrap();
-        let queue: &Box<InnerEventQueue<T>> = unsafe { transmute(any_queue) };
-        queue.receive()
+    /// Change the policy used to receive a registered event type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type has not been registered.
+    pub fn set_policy<T: Event>(&mut self, policy: EventPolicy<T>) {
+        self.get_queue_mut::<T>().policy = policy;
     }
 
-    /// Clear all events from the queue.
+    /// Register a new event type that is addressed to entities, so that the events can be
+    /// received by target.
+    pub fn register_targeted<T: TargetedEvent>(&mut self) {
+        self.queues.insert(TypeId::of::<T>(), Box::new(InnerEventQueue::<T>::new(Some(T::target))));
+    }
+
+    /// Iterate over all events of the given type that the current reader has not yet received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type has not been registered.
+    pub fn receive<T: Event>(&self) -> Iter<'_, T> {
+        self.get_queue::<T>().receive(self.reader)
+    }
+
+    /// Iterate over the events of the given type addressed to `entity` that the current reader has
+    /// not yet received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type has not been registered with `register_targeted`.
+    pub fn receive_for<T: TargetedEvent>(&self, entity: Entity) -> Iter<'_, T> {
+        self.get_targeted_queue::<T>().receive_routed(self.reader, |target| target == entity)
+    }
+
+    /// Iterate over the events of the given type addressed to entities with the components in
+    /// `filter` that the current reader has not yet received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Health(u32);
+    ///
+    /// components!(Health);
+    ///
+    /// pub struct Damage { pub target: Entity, pub amount: u32 }
+    ///
+    /// events!(Damage);
+    ///
+    /// impl TargetedEvent for Damage {
+    ///     fn target(&self) -> Entity {
+    ///         self.target
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut world = World::new();
+    ///     world.register::<Health>();
+    ///     let player = world.create();
+    ///     world.add(player, Health(100));
+    ///     let wall = world.create();
+    ///
+    ///     let mut queue = EventQueue::new();
+    ///     queue.register_targeted::<Damage>();
+    ///     let mut emitter = EventEmitter::new();
+    ///     emitter.register_targeted::<Damage>();
+    ///
+    ///     emitter.emit(Damage { target: wall, amount: 5 });
+    ///     emitter.emit(Damage { target: player, amount: 10 });
+    ///     queue.merge(&mut emitter);
+    ///
+    ///     let filter = ComponentFilter::new().with::<Health>();
+    ///     let damage = queue.receive_matching::<Damage>(&world, &filter)
+    ///         .map(|damage| damage.amount)
+    ///         .collect::<Vec<u32>>();
+    ///     assert_eq!(damage, vec![10]);
+    ///     assert_eq!(queue.receive_for::<Damage>(wall).count(), 1);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type has not been registered with `register_targeted`.
+    pub fn receive_matching<T: TargetedEvent>(&self, world: &World, filter: &ComponentFilter) -> Iter<'_, T> {
+        self.get_targeted_queue::<T>().receive_routed(self.reader, |target| world.matches(target, filter))
+    }
+
+    /// Discard the events that were emitted before the previous simulation step. An `EventQueue`
+    /// that is used outside of a `Simulation` does not track its readers, so every event is
+    /// discarded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Ping;
+    ///
+    /// events!(Ping);
+    ///
+    /// fn main() {
+    ///     let mut emitter = EventEmitter::new();
+    ///     emitter.register::<Ping>();
+    ///     let mut queue = EventQueue::new();
+    ///
+    ///     emitter.emit(Ping);
+    ///     queue.merge(&mut emitter);
+    ///     assert_eq!(queue.receive::<Ping>().count(), 1);
+    ///
+    ///     queue.flush();
+    ///     assert_eq!(queue.receive::<Ping>().count(), 0);
+    /// }
+    /// ```
     pub fn flush(&mut self) {
         for (_, any_queue) in self.queues.iter_mut() {
-            any_queue.flush();
+            if self.tracks_readers {
+                any_queue.flush();
+            } else {
+                any_queue.clear();
+            }
         }
     }
 
-    /// Merge events that were emitted into the queue.
+    /// Merge events that were emitted into the queue. Event types that have only been registered
+    /// with the `EventEmitter` are registered with the queue as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Ping;
+    ///
+    /// events!(Ping);
+    ///
+    /// fn main() {
+    ///     let mut emitter = EventEmitter::new();
+    ///     emitter.register::<Ping>();
+    ///     emitter.emit(Ping);
+    ///
+    ///     let mut queue = EventQueue::new();
+    ///     queue.merge(&mut emitter);
+    ///     assert_eq!(queue.receive::<Ping>().count(), 1);
+    /// }
+    /// ```
     pub fn merge(&mut self, emitter: &mut EventEmitter) {
-        for (family, any_emitter) in emitter.emitters.iter_mut() {
-            let any_queue = self.queues.get_mut(family).unwrap();
+        span!(DEBUG, "merge_events");
+        for (&event_type, any_emitter) in emitter.emitters.iter_mut() {
+            let any_queue = self.queues.entry(event_type).or_insert_with(|| any_emitter.new_queue());
             any_queue.merge(any_emitter);
         }
     }
+
+    /// Returns `true` if the event type has been registered, otherwise `false`.
+    pub(crate) fn is_registered<T: Event>(&self) -> bool {
+        self.queues.contains_key(&TypeId::of::<T>())
+    }
+
+    /// The events of the given type that are kept in the queue, and the position of each reader.
+    #[cfg(feature = "serde")]
+    pub(crate) fn retained<T: Event>(&self) -> (&[T], QueueState) {
+        let queue = self.get_queue::<T>();
+        let state = QueueState {
+            first: queue.first,
+            frame_start: queue.frame_start,
+            cursors: queue.cursors.iter().map(|(reader, &cursor)| (reader, cursor)).collect(),
+        };
+        (&queue.events, state)
+    }
+
+    /// Replace the events of the given type that are kept in the queue, and the position of each
+    /// reader.
+    #[cfg(feature = "serde")]
+    pub(crate) fn restore<T: Event>(&mut self, events: Vec<T>, state: QueueState) {
+        let queue = self.get_queue_mut::<T>();
+        queue.events = events;
+        queue.first = state.first;
+        queue.frame_start = state.frame_start;
+        queue.cursors = state.cursors.into_iter().collect();
+        queue.reroute();
+    }
+
+    /// Receive events as the given reader.
+    pub(crate) fn read_as(&mut self, reader: Reader) {
+        self.tracks_readers = true;
+        self.reader = reader;
+    }
+
+    /// Mark every event in the queue as received by the current reader.
+    pub(crate) fn advance(&mut self) {
+        for (_, any_queue) in self.queues.iter_mut() {
+            any_queue.advance(self.reader);
+        }
+    }
+
+    fn get_queue<T: Event>(&self) -> &InnerEventQueue<T> {
+        let any_queue = match self.queues.get(&TypeId::of::<T>()) {
+            Some(any_queue) => any_queue,
+            None => panic!("event type `{}` has not been registered", type_name::<T>()),
+        };
+        assert_eq!(any_queue.event_type(), TypeId::of::<T>());
+        unsafe { &*(&**any_queue as *const dyn AnyEventQueue as *const InnerEventQueue<T>) }
+    }
+
+    fn get_targeted_queue<T: TargetedEvent>(&self) -> &InnerEventQueue<T> {
+        let queue = self.get_queue::<T>();
+        if queue.router.is_none() {
+            panic!("event type `{}` has not been registered as a targeted event", type_name::<T>());
+        }
+        queue
+    }
+
+    fn get_queue_mut<T: Event>(&mut self) -> &mut InnerEventQueue<T> {
+        let any_queue = match self.queues.get_mut(&TypeId::of::<T>()) {
+            Some(any_queue) => any_queue,
+            None => panic!("event type `{}` has not been registered", type_name::<T>()),
+        };
+        assert_eq!(any_queue.event_type(), TypeId::of::<T>());
+        unsafe { &mut *(&mut **any_queue as *mut dyn AnyEventQueue as *mut InnerEventQueue<T>) }
+    }
+}
+
+impl Default for EventQueue {
+    fn default() -> EventQueue {
+        EventQueue::new()
+    }
 }
 
 /// Used to emit registered events.
 pub struct EventEmitter {
-    emitters: VecMap<Box<AnyEventEmitter>>,
+    emitters: HashMap<TypeId, Box<dyn AnyEventEmitter>>,
+    // The total number of events emitted, used to profile systems.
+    emitted: usize,
 }
 
 impl EventEmitter {
     /// Create a new, empty `EventEmitter`.
     pub fn new() -> EventEmitter {
         EventEmitter {
-            emitters: VecMap::new(),
+            emitters: HashMap::new(),
+            emitted: 0,
         }
     }
 
     /// Register a new event type.
-    pub fn register<T: 'static + FamilyMember>(&mut self) {
-        self.emitters.insert(T::family(), Box::new(InnerEventEmitter::<T>::new()));
+    pub fn register<T: Event>(&mut self) {
+        self.emitters.insert(TypeId::of::<T>(), Box::new(InnerEventEmitter::<T>::new(None)));
+    }
+
+    /// Register a new event type that is addressed to entities.
+    pub fn register_targeted<T: TargetedEvent>(&mut self) {
+        self.emitters.insert(TypeId::of::<T>(), Box::new(InnerEventEmitter::<T>::new(Some(T::target))));
     }
 
     /// Emit a registered event type.
-    pub fn emit<T: FamilyMember>(&mut self, event: T) {
-        let any_emitter = self.emitters.get_mut(T::family()).unwrap();
-        let emitter: &mut Box<InnerEventEmitter<T>> = unsafe { transmute(any_emitter) };
-        emitter.emit(event);
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type has not been registered.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use std::panic;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Ping;
+    ///
+    /// events!(Ping);
+    ///
+    /// fn main() {
+    ///     panic::set_hook(Box::new(|_| {}));
+    ///     let err = panic::catch_unwind(|| EventEmitter::new().emit(Ping)).unwrap_err();
+    ///     let message = err.downcast_ref::<String>().unwrap();
+    ///     assert!(message.starts_with("event type `"));
+    ///     assert!(message.ends_with("Ping` has not been registered"));
+    /// }
+    /// ```
+    pub fn emit<T: Event>(&mut self, event: T) {
+        self.get_emitter_mut::<T>().emit(event);
+        self.emitted += 1;
+    }
+
+    /// Call `handler` immediately each time an event of the given type is emitted, in addition to
+    /// queueing the event.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type has not been registered.
+    pub fn subscribe<T: Event, F: 'static + FnMut(&T)>(&mut self, handler: F) {
+        self.get_emitter_mut::<T>().handlers.push(Box::new(handler));
+    }
+
+    /// Add events of the given type that have already been emitted, without calling any handlers.
+    #[cfg(feature = "serde")]
+    pub(crate) fn restore_pending<T: Event>(&mut self, events: Vec<T>) {
+        self.get_emitter_mut::<T>().events.extend(events);
+    }
+
+    /// Events of the given type that have been emitted but not yet merged into a queue.
+    #[cfg(feature = "serde")]
+    pub(crate) fn pending<T: Event>(&self) -> &[T] {
+        &self.get_emitter::<T>().events
+    }
+
+    /// The total number of events that have been emitted.
+    pub(crate) fn emitted(&self) -> usize {
+        self.emitted
+    }
+
+    /// Returns `true` if the event type has been registered, otherwise `false`.
+    pub(crate) fn is_registered<T: Event>(&self) -> bool {
+        self.emitters.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Discard all events that have not yet been merged into a queue.
+    #[cfg(feature = "serde")]
+    pub(crate) fn clear(&mut self) {
+        for (_, any_emitter) in self.emitters.iter_mut() {
+            any_emitter.clear();
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn get_emitter<T: Event>(&self) -> &InnerEventEmitter<T> {
+        let any_emitter = self.emitters.get(&TypeId::of::<T>()).unwrap();
+        assert_eq!(any_emitter.event_type(), TypeId::of::<T>());
+        unsafe { &*(&**any_emitter as *const dyn AnyEventEmitter as *const InnerEventEmitter<T>) }
+    }
+
+    fn get_emitter_mut<T: Event>(&mut self) -> &mut InnerEventEmitter<T> {
+        let any_emitter = match self.emitters.get_mut(&TypeId::of::<T>()) {
+            Some(any_emitter) => any_emitter,
+            None => panic!("event type `{}` has not been registered", type_name::<T>()),
+        };
+        assert_eq!(any_emitter.event_type(), TypeId::of::<T>());
+        unsafe { &mut *(&mut **any_emitter as *mut dyn AnyEventEmitter as *mut InnerEventEmitter<T>) }
+    }
+}
+
+impl Default for EventEmitter {
+    fn default() -> EventEmitter {
+        EventEmitter::new()
     }
 }
 
+// Returns the type name of the event if it was dropped because its type is not registered.
+type Delivery = Box<dyn FnOnce(&mut EventEmitter) -> Option<&'static str> + Send>;
+
+/// A handle that lets other threads send events to a `Simulation`. The events are emitted at the
+/// start of the next `Simulation::update`, as though a `System` had emitted them.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+/// use std::thread;
+///
+/// use trex::*;
+///
+/// pub struct Input(pub String);
+///
+/// events!(Input);
+///
+/// fn main() {
+///     let mut builder = Simulation::builder();
+///     builder.add_event::<Input>();
+///     let mut simulation = builder.build().unwrap();
+///
+///     let sender = simulation.sender();
+///     thread::spawn(move || sender.send(Input(String::from("look")))).join().unwrap();
+///
+///     let received = Rc::new(Cell::new(false));
+///     let flag = received.clone();
+///     simulation.subscribe(move |_: &Input| flag.set(true));
+///     simulation.update(16.0);
+///     assert!(received.get());
+/// }
+/// ```
+#[derive(Clone)]
+pub struct EventSender {
+    tx: Sender<Delivery>,
+}
+
+impl EventSender {
+    /// Send an event to the `Simulation`. Returns `false` if the `Simulation` no longer exists.
+    ///
+    /// The event type must be registered with the `Simulation`'s `EventEmitter` by the time it is
+    /// delivered. Otherwise the event is dropped and reported by `Simulation::undelivered`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Unknown;
+    ///
+    /// events!(Unknown);
+    ///
+    /// fn main() {
+    ///     let mut simulation = Simulation::builder().build().unwrap();
+    ///     assert!(simulation.sender().send(Unknown));
+    ///     simulation.update(16.0);
+    ///     assert_eq!(simulation.undelivered().len(), 1);
+    ///     assert!(simulation.undelivered()[0].ends_with("Unknown"));
+    ///
+    ///     simulation.update(16.0);
+    ///     assert!(simulation.undelivered().is_empty());
+    /// }
+    /// ```
+    pub fn send<T: Event + Send>(&self, event: T) -> bool {
+        let delivery = move |emitter: &mut EventEmitter| {
+            if emitter.is_registered::<T>() {
+                emitter.emit(event);
+                None
+            } else {
+                Some(type_name::<T>())
+            }
+        };
+        self.tx.send(Box::new(delivery)).is_ok()
+    }
+}
+
+/// Collects the events sent through every `EventSender` until they are delivered.
+pub(crate) struct EventInbox {
+    tx: Sender<Delivery>,
+    rx: Receiver<Delivery>,
+}
+
+impl EventInbox {
+    pub fn new() -> EventInbox {
+        let (tx, rx) = channel();
+        EventInbox {
+            tx,
+            rx,
+        }
+    }
+
+    pub fn sender(&self) -> EventSender {
+        EventSender {
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Emit every event that has been sent so far. Returns the type names of the events that were
+    /// dropped because their types are not registered.
+    pub fn deliver(&self, emitter: &mut EventEmitter) -> Vec<&'static str> {
+        let mut dropped = Vec::new();
+        while let Ok(delivery) = self.rx.try_recv() {
+            dropped.extend(delivery(emitter));
+        }
+        dropped
+    }
+}
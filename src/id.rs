@@ -1,5 +1,10 @@
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 pub type Id = usize;
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct IdPool {
     reserved: Vec<bool>,
     released: Vec<Id>,
@@ -17,13 +22,16 @@ impl IdPool {
         id < self.reserved.len()
     }
 
-    pub fn reserved(&self) -> Iter {
+    pub fn reserved(&self) -> Iter<'_> {
         Iter::new(self)
     }
 
     pub fn reserve(&mut self) -> Id {
         match self.released.pop() {
-            Some(id) => id,
+            Some(id) => {
+                self.reserved[id] = true;
+                id
+            },
             None => {
                 let id = self.reserved.len();
                 self.reserved.push(true);
@@ -51,9 +59,9 @@ pub struct Iter<'a> {
 }
 
 impl<'a> Iter<'a> {
-    fn new(pool: &IdPool) -> Iter {
+    fn new(pool: &IdPool) -> Iter<'_> {
         Iter {
-            pool: pool,
+            pool,
             id: 0,
         }
     }
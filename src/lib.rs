@@ -51,7 +51,7 @@
 //!             pos.x += dx * dt_secs;
 //!             pos.y += dy * dt_secs;
 //!         }
-//!         emitter.emit(trex::Halt);
+//!         emitter.emit(trex::Halt::success());
 //!     }
 //! }
 //!
@@ -67,27 +67,22 @@
 //! }
 //!
 //! fn main() {
-//!     let world = {
-//!         let mut world = World::new();
-//!         world.register::<Position>();
-//!         world.register::<Velocity>();
-//!         world.register::<Acceleration>();
-//!
-//!         // Create an entity that accelerates in the x and y directions.
-//!         let entity = world.create();
-//!         world.tag(entity, "Test");
-//!         world.add(entity, Position { x: 1.0, y: 2.0 });
-//!         world.add(entity, Velocity { dx: 3.0, dy: 4.0 });
-//!         world.add(entity, Acceleration { ddx: 5.0, ddy: 6.0 });
-//!         world
-//!     };
-//!
-//!     let mut queue = EventQueue::new();
-//!     let mut emitter = EventEmitter::new();
-//!
-//!     let mut simulation = Simulation::new(world, queue, emitter);
-//!     simulation.register(PhysicsSystem::new());
-//!     simulation.register(TestSystem);
+//!     let mut builder = Simulation::builder();
+//!     builder.add_component::<Position>()
+//!         .add_component::<Velocity>()
+//!         .add_component::<Acceleration>()
+//!         .register(PhysicsSystem::new())
+//!         .register(TestSystem);
+//!
+//!     // Create an entity that accelerates in the x and y directions.
+//!     let world = builder.world_mut();
+//!     let entity = world.create();
+//!     world.tag(entity, "Test");
+//!     world.add(entity, Position { x: 1.0, y: 2.0 });
+//!     world.add(entity, Velocity { dx: 3.0, dy: 4.0 });
+//!     world.add(entity, Acceleration { ddx: 5.0, ddy: 6.0 });
+//!
+//!     let mut simulation = builder.build().unwrap();
 //!
 //!     // Run a single iteration of the simulation.
 //!     simulation.update(1000.0);
@@ -97,12 +92,49 @@
 
 extern crate vec_map;
 extern crate bit_set;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "serde")]
+extern crate ron;
+#[cfg(feature = "serde")]
+extern crate bincode;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+// Enters a `tracing` span at the given level until the end of the enclosing block. Does nothing
+// unless the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ( $level:ident, $( $arg:tt )+ ) => {
+        let _span = ::tracing::span!(::tracing::Level::$level, $( $arg )+).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ( $( $arg:tt )+ ) => {};
+}
 
+mod builder;
 mod component;
+mod condition;
+mod console;
 mod event;
 mod family;
 mod id;
+mod param;
+mod profile;
+#[cfg(feature = "serde")]
+mod prefab;
+#[cfg(feature = "serde")]
+mod record;
+mod remap;
+#[cfg(feature = "serde")]
+mod serialize;
 mod simulation;
+mod state;
 mod system;
 mod time;
 mod world;
@@ -110,9 +142,23 @@ mod world;
 #[macro_use]
 mod macros;
 
-pub use family::{Family, FamilyMember};
-pub use event::{EventQueue, EventEmitter};
-pub use simulation::{Halt, Simulation};
-pub use system::System;
-pub use time::calc_millis;
+pub use builder::{BuildError, Plugin, SimulationBuilder};
+pub use component::Component;
+pub use condition::{Every, OnEvent, ResourceChanged, RunCondition};
+pub use console::Console;
+pub use family::Family;
+pub use param::{Access, ParamFunction, Query, QueryData, QueryIter, Res, ResMut, SystemParam};
+pub use event::{Event, EventEmitter, EventPolicy, EventQueue, EventSender, TargetedEvent};
+#[cfg(feature = "serde")]
+pub use prefab::{Instance, Overrides, Prefab, Scene};
+#[cfg(feature = "serde")]
+pub use record::Recording;
+pub use profile::{Profile, Samples, Stats, SystemProfile};
+pub use remap::{EntityMap, MapEntities};
+#[cfg(feature = "serde")]
+pub use serialize::{SavedEntity, SavedWorld, SerializeError, TypeRegistry};
+pub use simulation::{Halt, Pause, Restart, Resume, Simulation};
+pub use state::{NextState, State};
+pub use system::{Requirement, Requirements, System, SystemId};
+pub use time::{calc_millis, Time};
 pub use world::{ComponentFilter, Entity, World};
@@ -4,7 +4,7 @@ macro_rules! family {
     ( @$family:expr, ) => {};
 
     ( @$family:expr, $head:ident, $( $tail:ident, )* ) => {
-        impl $crate::FamilyMember for $head {
+        impl $crate::Component for $head {
             fn family() -> $crate::Family {
                 $family
             }
@@ -18,7 +18,7 @@ macro_rules! family {
     };
 }
 
-/// Defines the component family.
+/// Defines the component classes.
 #[macro_export]
 macro_rules! components {
     ( $( $T:ident ), + ) => {
@@ -26,12 +26,49 @@ macro_rules! components {
     }
 }
 
-/// Defines the event family.
+/// Defines the event types. Event types are independent of the component classes, so a type can
+/// be both.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use trex::*;
+///
+/// pub struct Position { pub x: i32 }
+///
+/// pub struct Damage(pub u32);
+///
+/// pub struct Heal(pub u32);
+///
+/// components!(Position, Damage);
+/// events!(Damage, Heal);
+///
+/// fn main() {
+///     let mut world = World::new();
+///     world.register::<Position>();
+///     world.register::<Damage>();
+///     let entity = world.create();
+///     world.add(entity, Damage(3));
+///
+///     let mut emitter = EventEmitter::new();
+///     emitter.register::<Damage>();
+///     emitter.register::<Heal>();
+///     emitter.emit(Damage(5));
+///     emitter.emit(Heal(2));
+///
+///     let mut queue = EventQueue::new();
+///     queue.merge(&mut emitter);
+///     assert_eq!(queue.receive::<Damage>().map(|damage| damage.0).collect::<Vec<_>>(), vec![5]);
+///     assert_eq!(queue.receive::<Heal>().map(|heal| heal.0).collect::<Vec<_>>(), vec![2]);
+///     assert_eq!(world.get::<Damage>(entity).unwrap().0, 3);
+/// }
+/// ```
 #[macro_export]
 macro_rules! events {
     ( $( $T:ident ),+ ) => {
-        // Families start at 1 since the Halt event is automatically registered
-        // at 0.
-        family!(@1, $( $T, )+ );
+        $( impl $crate::Event for $T {} )+
     }
 }
@@ -0,0 +1,458 @@
+use std::any::{type_name, TypeId};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::slice::Iter as SliceIter;
+
+use super::component::Component;
+use super::event::{EventEmitter, EventQueue};
+use super::family::Family;
+use super::system::Requirements;
+use super::world::{ComponentFilter, Entity, World};
+
+/// The components, resources and events borrowed by the parameters of a function system.
+/// Registering a function whose parameters borrow the same value mutably more than once, or both
+/// mutably and immutably, panics.
+pub struct Access {
+    components: Vec<(Family, bool)>,
+    resources: Vec<(TypeId, bool)>,
+    emitter: bool,
+    requirements: Requirements,
+}
+
+impl Access {
+    pub(crate) fn new() -> Access {
+        Access {
+            components: Vec::new(),
+            resources: Vec::new(),
+            emitter: false,
+            requirements: Requirements::new(),
+        }
+    }
+
+    /// The components and resources that must exist for the parameters to be fetched.
+    pub(crate) fn into_requirements(self) -> Requirements {
+        self.requirements
+    }
+
+    fn component<C: 'static + Component>(&mut self, mutable: bool) {
+        for &(family, other_mutable) in &self.components {
+            if family == C::family() && (mutable || other_mutable) {
+                panic!("component `{}` is borrowed mutably while it is already borrowed", type_name::<C>());
+            }
+        }
+        self.components.push((C::family(), mutable));
+        self.requirements.component::<C>();
+    }
+
+    fn resource<R: 'static>(&mut self, mutable: bool) {
+        for &(resource, other_mutable) in &self.resources {
+            if resource == TypeId::of::<R>() && (mutable || other_mutable) {
+                panic!("resource `{}` is borrowed mutably while it is already borrowed", type_name::<R>());
+            }
+        }
+        self.resources.push((TypeId::of::<R>(), mutable));
+        self.requirements.resource::<R>();
+    }
+
+    fn emitter(&mut self) {
+        if self.emitter {
+            panic!("the `EventEmitter` is borrowed mutably more than once");
+        }
+        self.emitter = true;
+    }
+}
+
+/// Implemented by the types that can be the parameters of a function system.
+///
+/// # Safety
+///
+/// `access` must record everything that `fetch` borrows, so that conflicting parameters are
+/// rejected when the function is registered.
+pub unsafe trait SystemParam {
+    /// The value passed to the function.
+    type Item<'w>;
+
+    /// Record what the parameter borrows.
+    fn access(access: &mut Access);
+
+    /// Borrow the parameter for a single update.
+    ///
+    /// # Safety
+    ///
+    /// The pointers must be valid for `'w`, and nothing else may borrow what the parameter
+    /// records in `access` during `'w`.
+    unsafe fn fetch<'w>(world: *mut World, queue: &'w EventQueue, emitter: *mut EventEmitter) -> Self::Item<'w>;
+}
+
+unsafe impl SystemParam for &EventQueue {
+    type Item<'w> = &'w EventQueue;
+
+    fn access(_access: &mut Access) {}
+
+    unsafe fn fetch<'w>(_world: *mut World, queue: &'w EventQueue, _emitter: *mut EventEmitter) -> Self::Item<'w> {
+        queue
+    }
+}
+
+unsafe impl SystemParam for &mut EventEmitter {
+    type Item<'w> = &'w mut EventEmitter;
+
+    fn access(access: &mut Access) {
+        access.emitter();
+    }
+
+    unsafe fn fetch<'w>(_world: *mut World, _queue: &'w EventQueue, emitter: *mut EventEmitter) -> Self::Item<'w> {
+        &mut *emitter
+    }
+}
+
+/// A function system parameter that borrows a resource.
+///
+/// # Panics
+///
+/// The system panics if the resource does not exist when it is updated.
+pub struct Res<'w, R: 'w> {
+    value: &'w R,
+}
+
+impl<'w, R> Deref for Res<'w, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        self.value
+    }
+}
+
+unsafe impl<R: 'static> SystemParam for Res<'_, R> {
+    type Item<'w> = Res<'w, R>;
+
+    fn access(access: &mut Access) {
+        access.resource::<R>(false);
+    }
+
+    unsafe fn fetch<'w>(world: *mut World, _queue: &'w EventQueue, _emitter: *mut EventEmitter) -> Self::Item<'w> {
+        match (*world).resource::<R>() {
+            Some(value) => Res { value },
+            None => panic!("resource `{}` does not exist", type_name::<R>()),
+        }
+    }
+}
+
+/// A function system parameter that borrows a resource mutably.
+///
+/// # Panics
+///
+/// The system panics if the resource does not exist when it is updated.
+pub struct ResMut<'w, R: 'w> {
+    value: &'w mut R,
+}
+
+impl<'w, R> Deref for ResMut<'w, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        self.value
+    }
+}
+
+impl<'w, R> DerefMut for ResMut<'w, R> {
+    fn deref_mut(&mut self) -> &mut R {
+        self.value
+    }
+}
+
+unsafe impl<R: 'static> SystemParam for ResMut<'_, R> {
+    type Item<'w> = ResMut<'w, R>;
+
+    fn access(access: &mut Access) {
+        access.resource::<R>(true);
+    }
+
+    unsafe fn fetch<'w>(world: *mut World, _queue: &'w EventQueue, _emitter: *mut EventEmitter) -> Self::Item<'w> {
+        match (*world).resource_mut::<R>() {
+            Some(value) => ResMut { value },
+            None => panic!("resource `{}` does not exist", type_name::<R>()),
+        }
+    }
+}
+
+/// Implemented by the types that a `Query` can fetch for each `Entity`: `Entity` itself, `&C`
+/// and `&mut C` for a component class `C`, and tuples of them.
+///
+/// # Safety
+///
+/// `access` must record every component that `fetch` borrows, and `fetch` must not borrow the
+/// whole `World`, since the items fetched for other entities are still alive.
+pub unsafe trait QueryData {
+    /// The value fetched for each `Entity`.
+    type Item<'a>;
+
+    /// Extend the filter with the components that an `Entity` must have.
+    fn filter(filter: ComponentFilter) -> ComponentFilter;
+
+    /// Record what the query borrows.
+    fn access(access: &mut Access);
+
+    /// Fetch the value for an `Entity` that matches the filter.
+    ///
+    /// # Safety
+    ///
+    /// The `World` must be valid for `'a`, and nothing else may borrow what the query records in
+    /// `access` for the same `Entity` during `'a`.
+    unsafe fn fetch<'a>(world: *mut World, entity: Entity) -> Self::Item<'a>;
+}
+
+unsafe impl QueryData for Entity {
+    type Item<'a> = Entity;
+
+    fn filter(filter: ComponentFilter) -> ComponentFilter {
+        filter
+    }
+
+    fn access(_access: &mut Access) {}
+
+    unsafe fn fetch<'a>(_world: *mut World, entity: Entity) -> Self::Item<'a> {
+        entity
+    }
+}
+
+unsafe impl<C: 'static + Component> QueryData for &C {
+    type Item<'a> = &'a C;
+
+    fn filter(filter: ComponentFilter) -> ComponentFilter {
+        filter.with::<C>()
+    }
+
+    fn access(access: &mut Access) {
+        access.component::<C>(false);
+    }
+
+    unsafe fn fetch<'a>(world: *mut World, entity: Entity) -> Self::Item<'a> {
+        &*World::component_ptr::<C>(world, entity).unwrap()
+    }
+}
+
+unsafe impl<C: 'static + Component> QueryData for &mut C {
+    type Item<'a> = &'a mut C;
+
+    fn filter(filter: ComponentFilter) -> ComponentFilter {
+        filter.with::<C>()
+    }
+
+    fn access(access: &mut Access) {
+        access.component::<C>(true);
+    }
+
+    unsafe fn fetch<'a>(world: *mut World, entity: Entity) -> Self::Item<'a> {
+        &mut *World::component_ptr::<C>(world, entity).unwrap()
+    }
+}
+
+macro_rules! impl_query_data {
+    ($($Q:ident),+) => {
+        unsafe impl<$($Q: QueryData),+> QueryData for ($($Q,)+) {
+            type Item<'a> = ($($Q::Item<'a>,)+);
+
+            fn filter(filter: ComponentFilter) -> ComponentFilter {
+                $(let filter = $Q::filter(filter);)+
+                filter
+            }
+
+            fn access(access: &mut Access) {
+                $($Q::access(access);)+
+            }
+
+            unsafe fn fetch<'a>(world: *mut World, entity: Entity) -> Self::Item<'a> {
+                ($($Q::fetch(world, entity),)+)
+            }
+        }
+    };
+}
+
+impl_query_data!(A);
+impl_query_data!(A, B);
+impl_query_data!(A, B, C);
+impl_query_data!(A, B, C, D);
+impl_query_data!(A, B, C, D, E);
+impl_query_data!(A, B, C, D, E, F);
+
+/// A function system parameter that fetches the components of every `Entity` that has all of
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+///
+/// use trex::*;
+///
+/// pub struct Position { pub x: f32 }
+///
+/// pub struct Velocity { pub dx: f32 }
+///
+/// components!(Position, Velocity);
+///
+/// fn movement(mut query: Query<(&mut Position, &Velocity)>, time: Res<Time>) {
+///     for (position, velocity) in &mut query {
+///         position.x += velocity.dx * time.delta / 1000.0;
+///     }
+/// }
+///
+/// fn check(mut query: Query<(Entity, &Position)>, emitter: &mut EventEmitter) {
+///     for (_entity, position) in &mut query {
+///         assert_eq!(position.x, 3.0);
+///     }
+///     emitter.emit(Halt::success());
+/// }
+///
+/// fn main() {
+///     let mut builder = Simulation::builder();
+///     builder.add_component::<Position>()
+///         .add_component::<Velocity>()
+///         .register_param_fn(movement)
+///         .register_param_fn(check);
+///
+///     let world = builder.world_mut();
+///     let entity = world.create();
+///     world.add(entity, Position { x: 1.0 });
+///     world.add(entity, Velocity { dx: 2.0 });
+///
+///     let mut simulation = builder.build().unwrap();
+///     simulation.update(1000.0);
+///     assert!(simulation.halt());
+/// }
+/// ```
+pub struct Query<'w, Q> {
+    world: *mut World,
+    entities: Vec<Entity>,
+    _borrow: PhantomData<(&'w mut World, Q)>,
+}
+
+impl<'w, Q: QueryData> Query<'w, Q> {
+    /// The entities that match the query.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// The number of entities that match the query.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns `true` if no entities match the query, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Fetch the components of an `Entity`, if it matches the query.
+    pub fn get(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        if self.entities.contains(&entity) {
+            Some(unsafe { Q::fetch(self.world, entity) })
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the components of every `Entity` that matches the query.
+    pub fn iter_mut(&mut self) -> QueryIter<'_, Q> {
+        QueryIter {
+            world: self.world,
+            entities: self.entities.iter(),
+            _borrow: PhantomData,
+        }
+    }
+}
+
+impl<'q, 'w, Q: QueryData> IntoIterator for &'q mut Query<'w, Q> {
+    type Item = Q::Item<'q>;
+    type IntoIter = QueryIter<'q, Q>;
+
+    fn into_iter(self) -> QueryIter<'q, Q> {
+        self.iter_mut()
+    }
+}
+
+unsafe impl<Q: QueryData> SystemParam for Query<'_, Q> {
+    type Item<'w> = Query<'w, Q>;
+
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+
+    unsafe fn fetch<'w>(world: *mut World, _queue: &'w EventQueue, _emitter: *mut EventEmitter) -> Self::Item<'w> {
+        let entities = (*world).filter(&Q::filter(ComponentFilter::new()));
+        Query {
+            world,
+            entities,
+            _borrow: PhantomData,
+        }
+    }
+}
+
+/// Iterates over the components fetched by a `Query`.
+pub struct QueryIter<'q, Q> {
+    world: *mut World,
+    entities: SliceIter<'q, Entity>,
+    _borrow: PhantomData<&'q mut Q>,
+}
+
+impl<'q, Q: QueryData> Iterator for QueryIter<'q, Q> {
+    type Item = Q::Item<'q>;
+
+    fn next(&mut self) -> Option<Q::Item<'q>> {
+        // Each `Entity` is visited once, so the fetched components never alias.
+        self.entities.next().map(|&entity| unsafe { Q::fetch(self.world, entity) })
+    }
+}
+
+/// Implemented by functions whose parameters all implement `SystemParam`, so that they can be
+/// registered with `Simulation::register_param_fn`.
+///
+/// # Safety
+///
+/// `access` must record everything that `run` borrows.
+pub unsafe trait ParamFunction<Params> {
+    /// Record what the parameters borrow.
+    fn access(access: &mut Access);
+
+    /// Fetch the parameters and call the function.
+    ///
+    /// # Safety
+    ///
+    /// The pointers must be valid for the duration of the call, and the parameters must not
+    /// conflict with each other.
+    unsafe fn run(&mut self, world: *mut World, queue: &EventQueue, emitter: *mut EventEmitter);
+}
+
+macro_rules! impl_param_function {
+    ($($P:ident),*) => {
+        unsafe impl<Func, $($P: SystemParam),*> ParamFunction<($($P,)*)> for Func
+            where Func: FnMut($($P),*) + FnMut($($P::Item<'_>),*) {
+            #[allow(unused_variables)]
+            fn access(access: &mut Access) {
+                $($P::access(access);)*
+            }
+
+            #[allow(non_snake_case, unused_variables)]
+            unsafe fn run(&mut self, world: *mut World, queue: &EventQueue, emitter: *mut EventEmitter) {
+                // Calling through a generic function lets the compiler pick the `FnMut`
+                // implementation that takes the fetched items.
+                fn call<$($P),*>(mut func: impl FnMut($($P),*), $($P: $P),*) {
+                    func($($P),*)
+                }
+
+                $(let $P = $P::fetch(world, queue, emitter);)*
+                call(self, $($P),*)
+            }
+        }
+    };
+}
+
+impl_param_function!();
+impl_param_function!(A);
+impl_param_function!(A, B);
+impl_param_function!(A, B, C);
+impl_param_function!(A, B, C, D);
+impl_param_function!(A, B, C, D, E);
+impl_param_function!(A, B, C, D, E, F);
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+use serde_json::{self, Value};
+use ron;
+
+use super::serialize::{SavedEntity, SavedWorld, SerializeError, TypeRegistry};
+use super::world::{Entity, World};
+
+/// Field values that replace those of a `Prefab`, keyed by component name. Nested objects are
+/// merged field by field, anything else is replaced.
+pub type Overrides = BTreeMap<String, Value>;
+
+/// A template for an `Entity`, its components and its children.
+///
+/// Components are written as maps from field names to values, keyed by the names they were given
+/// in the `TypeRegistry`. Components that refer to other entities use the position of the entity
+/// in the template, counting the root as `0` and its descendants in depth-first order. Spawning
+/// fails with `SerializeError::DanglingEntities` if a position is outside the template.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+/// extern crate serde;
+/// extern crate serde_json;
+///
+/// use serde::{Serialize, Deserialize};
+/// use trex::*;
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Room { pub name: String, pub entities: Vec<Entity> }
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Actor { pub room: Entity }
+///
+/// impl MapEntities for Room {
+///     fn map_entities(&mut self, map: &mut EntityMap) {
+///         for entity in &mut self.entities {
+///             *entity = map.remap(*entity);
+///         }
+///     }
+/// }
+///
+/// impl MapEntities for Actor {
+///     fn map_entities(&mut self, map: &mut EntityMap) {
+///         self.room = map.remap(self.room);
+///     }
+/// }
+///
+/// components!(Room, Actor);
+///
+/// fn main() {
+///     let mut registry = TypeRegistry::new();
+///     registry.register_mapped::<Room>("Room");
+///     registry.register_mapped::<Actor>("Actor");
+///
+///     let mut world = World::new();
+///     world.register::<Room>();
+///     world.register::<Actor>();
+///
+///     let prefab = Prefab::from_ron(r#"(
+///         components: {
+///             "Room": { "name": "Room", "entities": [1] },
+///         },
+///         children: [
+///             (tag: Some("Guard"), components: { "Actor": { "room": 0 } }),
+///         ],
+///     )"#).unwrap();
+///
+///     let mut overrides = Overrides::new();
+///     overrides.insert(String::from("Room"), serde_json::json!({ "name": "Entrance" }));
+///     let entrance = prefab.spawn_with(&registry, &mut world, &overrides).unwrap();
+///
+///     let guard = world.lookup("Guard").unwrap();
+///     assert_eq!(world.get::<Actor>(guard).unwrap().room, entrance);
+///     assert_eq!(world.get::<Room>(entrance).unwrap().name, "Entrance");
+///     assert_eq!(world.get::<Room>(entrance).unwrap().entities, vec![guard]);
+///
+///     // Positions outside the template are rejected rather than kept as ids.
+///     let broken = Prefab::from_ron(r#"(components: { "Actor": { "room": 3 } })"#).unwrap();
+///     assert!(broken.spawn(&registry, &mut world).is_err());
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Prefab {
+    /// The tag given to each spawned instance, if any.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// The components of the `Entity`, keyed by their registered names.
+    #[serde(default)]
+    pub components: BTreeMap<String, Value>,
+    /// Entities that are spawned along with this one.
+    #[serde(default)]
+    pub children: Vec<Prefab>,
+}
+
+impl Prefab {
+    /// Parse a `Prefab` from RON.
+    pub fn from_ron(text: &str) -> Result<Prefab, SerializeError> {
+        Ok(ron::de::from_str(text)?)
+    }
+
+    /// Parse a `Prefab` from JSON.
+    pub fn from_json(json: &str) -> Result<Prefab, SerializeError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Spawn an instance of the `Prefab` into the `World`. Returns the root `Entity`.
+    pub fn spawn(&self, registry: &TypeRegistry, world: &mut World) -> Result<Entity, SerializeError> {
+        self.spawn_with(registry, world, &Overrides::new())
+    }
+
+    /// Spawn an instance of the `Prefab` into the `World`, replacing the given fields of the
+    /// root's components. Returns the root `Entity`.
+    pub fn spawn_with(&self, registry: &TypeRegistry, world: &mut World, overrides: &Overrides)
+        -> Result<Entity, SerializeError> {
+        self.spawn_tagged(registry, world, overrides, None)
+    }
+
+    // The tag of an `Instance` replaces the tag of the root before it is loaded, so that it is
+    // never taken from another instance of the same `Prefab`.
+    fn spawn_tagged(&self, registry: &TypeRegistry, world: &mut World, overrides: &Overrides, tag: Option<&String>)
+        -> Result<Entity, SerializeError> {
+        let mut saved = SavedWorld { entities: Vec::new() };
+        self.flatten(&mut saved.entities);
+
+        let root = &mut saved.entities[0];
+        if let Some(tag) = tag {
+            root.tag = Some(tag.clone());
+        }
+        for (name, patch) in overrides {
+            let value = root.components.entry(name.clone()).or_insert(Value::Null);
+            merge(value, patch);
+        }
+
+        let map = registry.load(world, &saved)?;
+        Ok(map.get(0).unwrap())
+    }
+
+    fn flatten(&self, entities: &mut Vec<SavedEntity>) {
+        entities.push(SavedEntity {
+            id: entities.len(),
+            tag: self.tag.clone(),
+            components: self.components.clone(),
+        });
+
+        for child in &self.children {
+            child.flatten(entities);
+        }
+    }
+}
+
+fn merge(value: &mut Value, patch: &Value) {
+    match (value, patch) {
+        (&mut Value::Object(ref mut fields), Value::Object(patches)) => {
+            for (name, patch) in patches {
+                merge(fields.entry(name.clone()).or_insert(Value::Null), patch);
+            }
+        },
+        (value, patch) => *value = patch.clone(),
+    }
+}
+
+/// A single instance of a `Prefab` in a `Scene`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Instance {
+    /// The name of the `Prefab` in the `Scene`.
+    pub prefab: String,
+    /// Replaces the tag of the `Prefab`, if any.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Fields that replace those of the root's components.
+    #[serde(default)]
+    pub overrides: Overrides,
+}
+
+/// A collection of named prefabs and the instances of them to spawn.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Scene {
+    #[serde(default)]
+    pub prefabs: BTreeMap<String, Prefab>,
+    #[serde(default)]
+    pub instances: Vec<Instance>,
+}
+
+impl Scene {
+    /// Parse a `Scene` from RON.
+    pub fn from_ron(text: &str) -> Result<Scene, SerializeError> {
+        Ok(ron::de::from_str(text)?)
+    }
+
+    /// Parse a `Scene` from JSON.
+    pub fn from_json(json: &str) -> Result<Scene, SerializeError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Spawn every instance in the `Scene` into the `World`. Returns the root `Entity` of each
+    /// instance, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Health { pub current: u32, pub max: u32 }
+    ///
+    /// components!(Health);
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register::<Health>("Health");
+    ///
+    ///     let mut world = World::new();
+    ///     world.register::<Health>();
+    ///
+    ///     let scene = Scene::from_ron(r#"(
+    ///         prefabs: {
+    ///             "Goblin": (tag: Some("Goblin"), components: { "Health": { "current": 5, "max": 5 } }),
+    ///         },
+    ///         instances: [
+    ///             (prefab: "Goblin"),
+    ///             (prefab: "Goblin", tag: Some("Chief"), overrides: { "Health": { "max": 20 } }),
+    ///         ],
+    ///     )"#).unwrap();
+    ///     let goblins = scene.spawn(&registry, &mut world).unwrap();
+    ///
+    ///     assert_eq!(world.lookup("Goblin"), Some(goblins[0]));
+    ///     assert_eq!(world.lookup("Chief"), Some(goblins[1]));
+    ///     assert_eq!(world.get::<Health>(goblins[1]).unwrap().max, 20);
+    ///
+    ///     let broken = Scene::from_ron(r#"(instances: [(prefab: "Dragon")])"#).unwrap();
+    ///     assert!(broken.spawn(&registry, &mut world).is_err());
+    /// }
+    /// ```
+    pub fn spawn(&self, registry: &TypeRegistry, world: &mut World) -> Result<Vec<Entity>, SerializeError> {
+        for instance in &self.instances {
+            if !self.prefabs.contains_key(&instance.prefab) {
+                return Err(SerializeError::UnknownPrefab(instance.prefab.clone()));
+            }
+        }
+
+        let mut entities = Vec::new();
+        for instance in &self.instances {
+            let prefab = &self.prefabs[&instance.prefab];
+            let entity = prefab.spawn_tagged(registry, world, &instance.overrides, instance.tag.as_ref())?;
+            entities.push(entity);
+        }
+        Ok(entities)
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::system::SystemId;
+
+/// Summary of the samples in a `Samples` window.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// The smallest sample.
+    pub min: f32,
+    /// The mean of the samples.
+    pub avg: f32,
+    /// The largest sample.
+    pub max: f32,
+    /// The 99th percentile of the samples.
+    pub p99: f32,
+}
+
+/// The most recent values of a measurement, such as the time taken by a `System`.
+pub struct Samples {
+    values: VecDeque<f32>,
+    window: usize,
+}
+
+impl Samples {
+    fn new(window: usize) -> Samples {
+        Samples {
+            values: VecDeque::with_capacity(window),
+            window,
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        if self.values.len() == self.window {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    /// The number of samples in the window.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if there are no samples, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The most recent sample, if any.
+    pub fn last(&self) -> Option<f32> {
+        self.values.back().cloned()
+    }
+
+    /// The statistics of the samples in the window. All are zero if there are no samples.
+    pub fn stats(&self) -> Stats {
+        if self.values.is_empty() {
+            return Stats::default();
+        }
+
+        let mut sorted = self.values.iter().cloned().collect::<Vec<f32>>();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let rank = (sorted.len() as f32 * 0.99).ceil() as usize;
+        Stats {
+            min: sorted[0],
+            avg: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            max: sorted[sorted.len() - 1],
+            p99: sorted[rank.max(1) - 1],
+        }
+    }
+}
+
+/// The measurements of a single `System`, taken each time it runs.
+pub struct SystemProfile {
+    id: SystemId,
+    name: &'static str,
+    time: Samples,
+    entities: Samples,
+    events: Samples,
+}
+
+impl SystemProfile {
+    fn new(id: SystemId, name: &'static str, window: usize) -> SystemProfile {
+        SystemProfile {
+            id,
+            name,
+            time: Samples::new(window),
+            entities: Samples::new(window),
+            events: Samples::new(window),
+        }
+    }
+
+    /// The id of the `System`.
+    pub fn id(&self) -> SystemId {
+        self.id
+    }
+
+    /// The type name of the `System`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The wall time in milliseconds taken by `System::update`.
+    pub fn time(&self) -> &Samples {
+        &self.time
+    }
+
+    /// The number of entities returned by `World::filter`, including those matched by a `Query`.
+    pub fn entities(&self) -> &Samples {
+        &self.entities
+    }
+
+    /// The number of events emitted.
+    pub fn events(&self) -> &Samples {
+        &self.events
+    }
+}
+
+/// Rolling statistics of the time taken by each step of a `Simulation` and each of its systems.
+/// Started with `Simulation::start_profiling`.
+///
+/// The `Display` implementation formats the statistics as a text report.
+pub struct Profile {
+    window: usize,
+    frame: Samples,
+    systems: Vec<SystemProfile>,
+}
+
+impl Profile {
+    pub(crate) fn new(window: usize) -> Profile {
+        assert!(window > 0, "the profiling window must hold at least one sample");
+        Profile {
+            window,
+            frame: Samples::new(window),
+            systems: Vec::new(),
+        }
+    }
+
+    /// The number of samples that are kept of each measurement.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// The wall time in milliseconds taken by `Simulation::update`.
+    pub fn frame(&self) -> &Samples {
+        &self.frame
+    }
+
+    /// The measurements of every `System` that has run since profiling started.
+    pub fn systems(&self) -> &[SystemProfile] {
+        &self.systems
+    }
+
+    /// The measurements of the given `System`, if it has run since profiling started.
+    pub fn system(&self, id: SystemId) -> Option<&SystemProfile> {
+        self.systems.iter().find(|profile| profile.id == id)
+    }
+
+    pub(crate) fn record_frame(&mut self, time: f32) {
+        self.frame.push(time);
+    }
+
+    pub(crate) fn record_system(&mut self, id: SystemId, name: &'static str, time: f32, entities: usize, events: usize) {
+        let index = match self.systems.iter().position(|profile| profile.id == id) {
+            Some(index) => index,
+            None => {
+                self.systems.push(SystemProfile::new(id, name, self.window));
+                self.systems.len() - 1
+            },
+        };
+
+        let profile = &mut self.systems[index];
+        profile.time.push(time);
+        profile.entities.push(entities as f32);
+        profile.events.push(events as f32);
+    }
+
+    /// Discard the measurements of a `System` that has been removed or replaced.
+    pub(crate) fn forget(&mut self, id: SystemId) {
+        self.systems.retain(|profile| profile.id != id);
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.systems.iter()
+            .map(|profile| profile.name.len())
+            .fold("frame".len(), usize::max);
+
+        writeln!(f, "{:<width$} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "", "min ms", "avg ms", "max ms", "p99 ms", "entities", "events", width = width)?;
+
+        let frame = self.frame.stats();
+        writeln!(f, "{:<width$} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            "frame", frame.min, frame.avg, frame.max, frame.p99, width = width)?;
+
+        for profile in &self.systems {
+            let time = profile.time.stats();
+            writeln!(f, "{:<width$} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.1} {:>9.1}",
+                profile.name, time.min, time.avg, time.max, time.p99,
+                profile.entities.stats().avg, profile.events.stats().avg, width = width)?;
+        }
+
+        Ok(())
+    }
+}
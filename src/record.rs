@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+use bincode;
+
+use super::event::EventEmitter;
+use super::serialize::{RecordedEvents, SerializeError, TypeRegistry};
+
+/// The events that entered the `Simulation` from outside at the start of a single step.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct Frame {
+    pub dt: f32,
+    pub events: RecordedEvents,
+}
+
+/// A session of a `Simulation` captured by `Simulation::start_recording`.
+///
+/// Only the events that enter the `Simulation` from outside are recorded, such as those emitted
+/// before an update or sent through an `EventSender`. Events emitted by systems are produced again
+/// when the recording is replayed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub(crate) start: Vec<u8>,
+    pub(crate) frames: Vec<Frame>,
+    pub(crate) end: Vec<u8>,
+}
+
+impl Recording {
+    /// The number of simulation steps in the recording.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns `true` if no simulation steps were recorded, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Encode the `Recording` as a compact binary blob.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializeError> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Decode a `Recording` from the blob produced by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Recording, SerializeError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Write the `Recording` to a file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::env;
+    /// use std::fs;
+    ///
+    /// use trex::*;
+    ///
+    /// let registry = TypeRegistry::new();
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// simulation.start_recording(&registry).unwrap();
+    /// simulation.update(16.0);
+    /// simulation.update(16.0);
+    /// let recording = simulation.stop_recording().unwrap();
+    ///
+    /// let path = env::temp_dir().join(format!("trex-recording-{}.bin", std::process::id()));
+    /// recording.save(&path).unwrap();
+    /// let loaded = Recording::load(&path).unwrap();
+    /// fs::remove_file(&path).unwrap();
+    /// assert_eq!(loaded, recording);
+    /// assert_eq!(loaded.len(), 2);
+    ///
+    /// match Recording::load(&path) {
+    ///     Err(SerializeError::Io(_)) => {},
+    ///     _ => panic!("expected the missing file to be reported"),
+    /// }
+    /// ```
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SerializeError> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Read a `Recording` from a file written by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Recording, SerializeError> {
+        Recording::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// Collects the frames of a `Recording` while the `Simulation` runs.
+pub(crate) struct Recorder {
+    registry: TypeRegistry,
+    start: Vec<u8>,
+    frames: Vec<Frame>,
+    // The first error that occurred while recording, reported when the recording stops.
+    error: Option<SerializeError>,
+}
+
+impl Recorder {
+    pub fn new(registry: TypeRegistry, start: Vec<u8>) -> Recorder {
+        Recorder {
+            registry,
+            start,
+            frames: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn registry(&self) -> &TypeRegistry {
+        &self.registry
+    }
+
+    /// Record the events that are waiting in the `EventEmitter` at the start of a step.
+    pub fn record(&mut self, emitter: &EventEmitter, dt: f32) {
+        if self.error.is_some() {
+            return;
+        }
+
+        match self.registry.record_events(emitter) {
+            Ok(events) => self.frames.push(Frame { dt, events }),
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    pub fn finish(self, end: Vec<u8>) -> Result<Recording, SerializeError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(Recording {
+                start: self.start,
+                frames: self.frames,
+                end,
+            }),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use super::world::Entity;
+
+/// Maps entities from one `World` onto the entities that replaced them in another.
+pub struct EntityMap {
+    map: HashMap<Entity, Entity>,
+    // The references found by `remap` since the last call to `attribute`.
+    unresolved: Vec<Entity>,
+    dangling: Vec<(Entity, Entity)>,
+}
+
+impl EntityMap {
+    /// Create an empty `EntityMap`.
+    pub fn new() -> EntityMap {
+        EntityMap {
+            map: HashMap::new(),
+            unresolved: Vec::new(),
+            dangling: Vec::new(),
+        }
+    }
+
+    /// Record that `from` has been replaced by `to`.
+    pub fn insert(&mut self, from: Entity, to: Entity) {
+        self.map.insert(from, to);
+    }
+
+    /// Get the `Entity` that replaced `from`, if any.
+    pub fn get(&self, from: Entity) -> Option<Entity> {
+        self.map.get(&from).cloned()
+    }
+
+    /// Get the `Entity` that replaced `from`. If it was not replaced, `from` is returned and the
+    /// reference is reported as dangling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = trex::EntityMap::new();
+    /// map.insert(3, 0);
+    /// assert_eq!(map.remap(3), 0);
+    /// assert_eq!(map.remap(4), 4);
+    /// ```
+    pub fn remap(&mut self, from: Entity) -> Entity {
+        match self.get(from) {
+            Some(to) => to,
+            None => {
+                self.unresolved.push(from);
+                from
+            },
+        }
+    }
+
+    /// The references that could not be remapped, as pairs of the `Entity` whose component holds
+    /// the reference and the `Entity` it refers to. The components keep the original ids, so it
+    /// is up to the caller whether to keep or remove them.
+    pub fn dangling(&self) -> &[(Entity, Entity)] {
+        &self.dangling
+    }
+
+    /// Report the references found by `remap` since the last call as held by `entity`.
+    pub(crate) fn attribute(&mut self, entity: Entity) {
+        for target in self.unresolved.drain(..) {
+            self.dangling.push((entity, target));
+        }
+    }
+}
+
+impl Default for EntityMap {
+    fn default() -> EntityMap {
+        EntityMap::new()
+    }
+}
+
+/// Implemented by components that refer to other entities, so that the references stay valid
+/// when entities are given new ids.
+pub trait MapEntities {
+    /// Replace every `Entity` stored in the component using `EntityMap::remap`.
+    fn map_entities(&mut self, map: &mut EntityMap);
+}
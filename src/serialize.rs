@@ -0,0 +1,872 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::io;
+
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use ron;
+use bincode;
+
+use super::event::{Event, EventEmitter, EventQueue, QueueState};
+use super::component::Component;
+use super::id::IdPool;
+use super::remap::{EntityMap, MapEntities};
+use super::simulation::Halt;
+use super::time::Time;
+use super::world::{ComponentFilter, Entity, World};
+
+/// The serialized form of a single `Entity`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SavedEntity {
+    /// The id of the `Entity` in the `World` it was saved from.
+    pub id: Entity,
+    /// The tag of the `Entity`, if it has one.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// The serialized components of the `Entity`, keyed by their registered names.
+    #[serde(default)]
+    pub components: BTreeMap<String, Value>,
+}
+
+/// The serialized form of a `World`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SavedWorld {
+    pub entities: Vec<SavedEntity>,
+}
+
+/// Errors that can occur while saving or loading a `World`.
+#[derive(Debug)]
+pub enum SerializeError {
+    /// A component could not be converted to or from JSON.
+    Json(serde_json::Error),
+    /// A component could not be converted to RON.
+    Ron(ron::Error),
+    /// The RON input could not be parsed.
+    RonSyntax(ron::error::SpannedError),
+    /// A snapshot could not be encoded or decoded.
+    Bincode(bincode::Error),
+    /// No component class has been registered under the given name.
+    UnknownComponent(String),
+    /// The component class with the given name has not been registered with the `World`.
+    Unregistered(String),
+    /// No event type has been registered under the given name.
+    UnknownEvent(String),
+    /// The event type with the given name has not been registered with the `EventQueue` and
+    /// `EventEmitter`.
+    UnregisteredEvent(String),
+    /// A `Scene` refers to a prefab that it does not contain.
+    UnknownPrefab(String),
+    /// No resource type has been registered under the given name.
+    UnknownResource(String),
+    /// Loaded components refer to entities that were not saved along with them, given as pairs of
+    /// the saved `Entity` that holds the reference and the `Entity` it refers to.
+    DanglingEntities(Vec<(Entity, Entity)>),
+    /// A recording could not be read or written.
+    Io(io::Error),
+    /// Replaying a recording did not reproduce the recorded state.
+    Diverged,
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SerializeError::Json(ref err) => write!(f, "json error: {}", err),
+            SerializeError::Ron(ref err) => write!(f, "ron error: {}", err),
+            SerializeError::RonSyntax(ref err) => write!(f, "ron error: {}", err),
+            SerializeError::Bincode(ref err) => write!(f, "bincode error: {}", err),
+            SerializeError::UnknownComponent(ref name) => write!(f, "unknown component: {}", name),
+            SerializeError::Unregistered(ref name) => {
+                write!(f, "component is not registered with the world: {}", name)
+            },
+            SerializeError::UnknownEvent(ref name) => write!(f, "unknown event: {}", name),
+            SerializeError::UnregisteredEvent(ref name) => write!(f, "event is not registered: {}", name),
+            SerializeError::UnknownPrefab(ref name) => write!(f, "unknown prefab: {}", name),
+            SerializeError::UnknownResource(ref name) => write!(f, "unknown resource: {}", name),
+            SerializeError::DanglingEntities(ref dangling) => {
+                write!(f, "references to entities that were not saved: {:?}", dangling)
+            },
+            SerializeError::Io(ref err) => write!(f, "io error: {}", err),
+            SerializeError::Diverged => write!(f, "replay diverged from the recording"),
+        }
+    }
+}
+
+impl error::Error for SerializeError {}
+
+impl From<serde_json::Error> for SerializeError {
+    fn from(err: serde_json::Error) -> SerializeError {
+        SerializeError::Json(err)
+    }
+}
+
+impl From<ron::Error> for SerializeError {
+    fn from(err: ron::Error) -> SerializeError {
+        SerializeError::Ron(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SerializeError {
+    fn from(err: ron::error::SpannedError) -> SerializeError {
+        SerializeError::RonSyntax(err)
+    }
+}
+
+impl From<io::Error> for SerializeError {
+    fn from(err: io::Error) -> SerializeError {
+        SerializeError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for SerializeError {
+    fn from(err: bincode::Error) -> SerializeError {
+        SerializeError::Bincode(err)
+    }
+}
+
+type SaveFn = fn(&World, Entity) -> Option<Result<Value, SerializeError>>;
+type LoadFn = fn(Value, Option<&mut EntityMap>) -> Result<AttachFn, SerializeError>;
+type EncodeFn = fn(&World, Entity) -> Option<Result<Vec<u8>, SerializeError>>;
+type DecodeFn = fn(&[u8]) -> Result<AttachFn, SerializeError>;
+type EncodeEventsFn = fn(&EventQueue, &EventEmitter) -> Result<EncodedEvents, SerializeError>;
+type DecodeEventsFn = fn(&EncodedEvents) -> Result<RestoreEventsFn, SerializeError>;
+
+// Values are decoded before anything is changed, so that an error leaves the `World` and the
+// events as they were. These attach the decoded values afterwards.
+type AttachFn = Box<dyn FnOnce(&mut World, Entity)>;
+type RestoreEventsFn = Box<dyn FnOnce(&mut EventQueue, &mut EventEmitter)>;
+type RestoreResourceFn = Box<dyn FnOnce(&mut World)>;
+type EncodeResourceFn = fn(&World) -> Option<Result<Vec<u8>, SerializeError>>;
+type DecodeResourceFn = fn(Option<&[u8]>) -> Result<RestoreResourceFn, SerializeError>;
+type RecordFn = fn(&EventEmitter) -> Result<Vec<Vec<u8>>, SerializeError>;
+type ReplayFn = fn(&mut EventEmitter, &[Vec<u8>]) -> Result<(), SerializeError>;
+
+#[derive(Clone)]
+struct ComponentEntry {
+    name: String,
+    is_registered: fn(&World) -> bool,
+    save: SaveFn,
+    load: LoadFn,
+    encode: EncodeFn,
+    decode: DecodeFn,
+}
+
+#[derive(Clone)]
+struct EventEntry {
+    name: String,
+    is_registered: fn(&EventQueue, &EventEmitter) -> bool,
+    encode: EncodeEventsFn,
+    decode: DecodeEventsFn,
+    record: RecordFn,
+    replay: ReplayFn,
+}
+
+#[derive(Clone)]
+struct ResourceEntry {
+    name: String,
+    encode: EncodeResourceFn,
+    decode: DecodeResourceFn,
+}
+
+fn is_registered<C: Component>(world: &World) -> bool {
+    world.is_registered::<C>()
+}
+
+fn save_component<C: Component + Serialize>(world: &World, entity: Entity)
+    -> Option<Result<Value, SerializeError>> {
+    if world.has::<C>(entity) {
+        world.get::<C>(entity).map(|component| serde_json::to_value(component).map_err(From::from))
+    } else {
+        None
+    }
+}
+
+fn load_component<C: 'static + Component + DeserializeOwned>(value: Value, _map: Option<&mut EntityMap>)
+    -> Result<AttachFn, SerializeError> {
+    let component = serde_json::from_value::<C>(value)?;
+    Ok(Box::new(move |world: &mut World, entity| world.add(entity, component)))
+}
+
+// References are only remapped when a map is given, which `load_component` does not.
+fn load_mapped<C: 'static + Component + MapEntities + DeserializeOwned>(value: Value, map: Option<&mut EntityMap>)
+    -> Result<AttachFn, SerializeError> {
+    let mut component = serde_json::from_value::<C>(value)?;
+    if let Some(map) = map {
+        component.map_entities(map);
+    }
+    Ok(Box::new(move |world: &mut World, entity| world.add(entity, component)))
+}
+
+fn encode_component<C: Component + Serialize>(world: &World, entity: Entity)
+    -> Option<Result<Vec<u8>, SerializeError>> {
+    if world.has::<C>(entity) {
+        world.get::<C>(entity).map(|component| bincode::serialize(component).map_err(From::from))
+    } else {
+        None
+    }
+}
+
+fn decode_component<C: 'static + Component + DeserializeOwned>(bytes: &[u8]) -> Result<AttachFn, SerializeError> {
+    let component = bincode::deserialize::<C>(bytes)?;
+    Ok(Box::new(move |world: &mut World, entity| world.add(entity, component)))
+}
+
+fn is_event_registered<T: Event>(queue: &EventQueue, emitter: &EventEmitter) -> bool {
+    queue.is_registered::<T>() && emitter.is_registered::<T>()
+}
+
+fn encode_events<T: Event + Serialize>(queue: &EventQueue, emitter: &EventEmitter)
+    -> Result<EncodedEvents, SerializeError> {
+    let (events, state) = queue.retained::<T>();
+
+    let mut retained = Vec::new();
+    for event in events {
+        retained.push(bincode::serialize(event)?);
+    }
+
+    let mut pending = Vec::new();
+    for event in emitter.pending::<T>() {
+        pending.push(bincode::serialize(event)?);
+    }
+
+    Ok(EncodedEvents { retained, state, pending })
+}
+
+fn decode_events<T: Event + DeserializeOwned>(encoded: &EncodedEvents) -> Result<RestoreEventsFn, SerializeError> {
+    let mut retained = Vec::new();
+    for bytes in &encoded.retained {
+        retained.push(bincode::deserialize::<T>(bytes)?);
+    }
+
+    let mut pending = Vec::new();
+    for bytes in &encoded.pending {
+        pending.push(bincode::deserialize::<T>(bytes)?);
+    }
+
+    let state = encoded.state.clone();
+    Ok(Box::new(move |queue: &mut EventQueue, emitter: &mut EventEmitter| {
+        queue.restore(retained, state);
+        emitter.restore_pending(pending);
+    }))
+}
+
+fn record_events<T: Event + Serialize>(emitter: &EventEmitter) -> Result<Vec<Vec<u8>>, SerializeError> {
+    let mut recorded = Vec::new();
+    if emitter.is_registered::<T>() {
+        for event in emitter.pending::<T>() {
+            recorded.push(bincode::serialize(event)?);
+        }
+    }
+    Ok(recorded)
+}
+
+fn replay_events<T: Event + DeserializeOwned>(emitter: &mut EventEmitter, recorded: &[Vec<u8>])
+    -> Result<(), SerializeError> {
+    for bytes in recorded {
+        emitter.emit(bincode::deserialize::<T>(bytes)?);
+    }
+    Ok(())
+}
+
+fn encode_resource<R: 'static + Serialize>(world: &World) -> Option<Result<Vec<u8>, SerializeError>> {
+    world.resource::<R>().map(|resource| bincode::serialize(resource).map_err(From::from))
+}
+
+// A resource that was missing from the `World` when the snapshot was taken is removed.
+fn decode_resource<R: 'static + DeserializeOwned>(bytes: Option<&[u8]>) -> Result<RestoreResourceFn, SerializeError> {
+    match bytes {
+        Some(bytes) => {
+            let resource = bincode::deserialize::<R>(bytes)?;
+            Ok(Box::new(move |world: &mut World| world.add_resource(resource)))
+        },
+        None => Ok(Box::new(|world: &mut World| {
+            world.remove_resource::<R>();
+        })),
+    }
+}
+
+/// Maps component names to the functions used to save and load them.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate trex;
+/// extern crate serde;
+///
+/// use serde::{Serialize, Deserialize};
+/// use trex::{EntityMap, MapEntities, TypeRegistry, World};
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Name(String);
+///
+/// #[derive(Serialize, Deserialize)]
+/// pub struct Follow(trex::Entity);
+///
+/// impl MapEntities for Follow {
+///     fn map_entities(&mut self, map: &mut EntityMap) {
+///         self.0 = map.remap(self.0);
+///     }
+/// }
+///
+/// components!(Name, Follow);
+///
+/// fn main() {
+///     let mut registry = TypeRegistry::new();
+///     registry.register::<Name>("Name");
+///     registry.register_mapped::<Follow>("Follow");
+///
+///     let mut world = World::new();
+///     world.register::<Name>();
+///     world.register::<Follow>();
+///     let leader = world.create();
+///     world.add(leader, Name(String::from("Leader")));
+///     let follower = world.create();
+///     world.tag(follower, "Follower");
+///     world.add(follower, Follow(leader));
+///     let json = registry.to_json(&world).unwrap();
+///
+///     let mut other = World::new();
+///     other.register::<Name>();
+///     other.register::<Follow>();
+///     other.create();
+///     registry.from_json(&mut other, &json).unwrap();
+///
+///     let follower = other.lookup("Follower").unwrap();
+///     let &Follow(leader) = other.get::<Follow>(follower).unwrap();
+///     assert_eq!(other.get::<Name>(leader).unwrap().0, "Leader");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct TypeRegistry {
+    components: Vec<ComponentEntry>,
+    names: HashMap<String, usize>,
+    events: Vec<EventEntry>,
+    event_names: HashMap<String, usize>,
+    resources: Vec<ResourceEntry>,
+    resource_names: HashMap<String, usize>,
+}
+
+impl TypeRegistry {
+    /// Create an empty `TypeRegistry`.
+    pub fn new() -> TypeRegistry {
+        TypeRegistry {
+            components: Vec::new(),
+            names: HashMap::new(),
+            events: Vec::new(),
+            event_names: HashMap::new(),
+            resources: Vec::new(),
+            resource_names: HashMap::new(),
+        }
+    }
+
+    /// Register a component class under the given name.
+    pub fn register<C>(&mut self, name: &str)
+        where C: 'static + Component + Serialize + DeserializeOwned {
+        self.insert(ComponentEntry {
+            name: name.to_owned(),
+            is_registered: is_registered::<C>,
+            save: save_component::<C>,
+            load: load_component::<C>,
+            encode: encode_component::<C>,
+            decode: decode_component::<C>,
+        });
+    }
+
+    /// Register a component class that refers to other entities under the given name. The
+    /// references are remapped when the component is loaded by `load`, and loading fails with
+    /// `SerializeError::DanglingEntities` if any of them are not among the saved entities.
+    pub fn register_mapped<C>(&mut self, name: &str)
+        where C: 'static + Component + MapEntities + Serialize + DeserializeOwned {
+        self.insert(ComponentEntry {
+            name: name.to_owned(),
+            is_registered: is_registered::<C>,
+            save: save_component::<C>,
+            load: load_mapped::<C>,
+            encode: encode_component::<C>,
+            decode: decode_component::<C>,
+        });
+    }
+
+    /// Register an event type under the given name, so that events that have not yet been
+    /// received can be included in snapshots.
+    pub fn register_event<T>(&mut self, name: &str)
+        where T: Event + Serialize + DeserializeOwned {
+        let entry = EventEntry {
+            name: name.to_owned(),
+            is_registered: is_event_registered::<T>,
+            encode: encode_events::<T>,
+            decode: decode_events::<T>,
+            record: record_events::<T>,
+            replay: replay_events::<T>,
+        };
+
+        match self.event_names.get(name).cloned() {
+            Some(index) => self.events[index] = entry,
+            None => {
+                self.event_names.insert(name.to_owned(), self.events.len());
+                self.events.push(entry);
+            },
+        }
+    }
+
+    /// Register a resource type under the given name, so that it is included in snapshots. The
+    /// `State` resources added by `Simulation::add_state` are only included if they are
+    /// registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+    /// pub enum Screen { Menu, Game }
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register_resource::<State<Screen>>("Screen");
+    ///
+    ///     let mut builder = Simulation::builder();
+    ///     builder.add_state(Screen::Menu);
+    ///     let mut simulation = builder.build().unwrap();
+    ///     let snapshot = simulation.snapshot(&registry).unwrap();
+    ///
+    ///     simulation.emit(NextState(Screen::Game));
+    ///     simulation.update(100.0);
+    ///     simulation.update(100.0);
+    ///     assert_eq!(simulation.world().resource::<State<Screen>>().unwrap().current(), &Screen::Game);
+    ///
+    ///     simulation.restore(&registry, &snapshot).unwrap();
+    ///     assert_eq!(simulation.world().resource::<State<Screen>>().unwrap().current(), &Screen::Menu);
+    ///     assert_eq!(simulation.world().resource::<Time>().unwrap().elapsed, 0.0);
+    /// }
+    /// ```
+    pub fn register_resource<R>(&mut self, name: &str)
+        where R: 'static + Serialize + DeserializeOwned {
+        let entry = ResourceEntry {
+            name: name.to_owned(),
+            encode: encode_resource::<R>,
+            decode: decode_resource::<R>,
+        };
+
+        match self.resource_names.get(name).cloned() {
+            Some(index) => self.resources[index] = entry,
+            None => {
+                self.resource_names.insert(name.to_owned(), self.resources.len());
+                self.resources.push(entry);
+            },
+        }
+    }
+
+    fn insert(&mut self, entry: ComponentEntry) {
+        match self.names.get(&entry.name).cloned() {
+            Some(index) => self.components[index] = entry,
+            None => {
+                self.names.insert(entry.name.clone(), self.components.len());
+                self.components.push(entry);
+            },
+        }
+    }
+
+    fn entry(&self, name: &str) -> Result<&ComponentEntry, SerializeError> {
+        match self.names.get(name) {
+            Some(&index) => Ok(&self.components[index]),
+            None => Err(SerializeError::UnknownComponent(name.to_owned())),
+        }
+    }
+
+    fn event_entry(&self, name: &str) -> Result<&EventEntry, SerializeError> {
+        match self.event_names.get(name) {
+            Some(&index) => Ok(&self.events[index]),
+            None => Err(SerializeError::UnknownEvent(name.to_owned())),
+        }
+    }
+
+    fn resource_entry(&self, name: &str) -> Result<&ResourceEntry, SerializeError> {
+        match self.resource_names.get(name) {
+            Some(&index) => Ok(&self.resources[index]),
+            None => Err(SerializeError::UnknownResource(name.to_owned())),
+        }
+    }
+
+    /// Save every `Entity` in the `World`, along with its tag and registered components.
+    pub fn save(&self, world: &World) -> Result<SavedWorld, SerializeError> {
+        let mut entities = Vec::new();
+        for entity in world.filter(&ComponentFilter::new()) {
+            entities.push(self.save_entity(world, entity)?);
+        }
+        Ok(SavedWorld { entities })
+    }
+
+    /// Save a single `Entity`, along with its tag and registered components.
+    pub fn save_entity(&self, world: &World, entity: Entity) -> Result<SavedEntity, SerializeError> {
+        let mut components = BTreeMap::new();
+        for entry in &self.components {
+            if let Some(value) = (entry.save)(world, entity) {
+                components.insert(entry.name.clone(), value?);
+            }
+        }
+
+        Ok(SavedEntity {
+            id: entity,
+            tag: world.tag_of(entity).map(|tag| tag.to_owned()),
+            components,
+        })
+    }
+
+    /// Save a single component of an `Entity`. Returns `None` if the `Entity` does not have it.
+    pub fn save_component(&self, world: &World, entity: Entity, name: &str) -> Result<Option<Value>, SerializeError> {
+        let entry = self.entry(name)?;
+        match (entry.save)(world, entity) {
+            Some(value) => Ok(Some(value?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Load a single component and attach it to an `Entity`, replacing any component of the
+    /// same class.
+    pub fn load_component(&self, world: &mut World, entity: Entity, name: &str, value: Value)
+        -> Result<(), SerializeError> {
+        let entry = self.entry(name)?;
+        if !(entry.is_registered)(world) {
+            return Err(SerializeError::Unregistered(name.to_owned()));
+        }
+        let attach = (entry.load)(value, None)?;
+        attach(world, entity);
+        Ok(())
+    }
+
+    /// Load the saved entities into the `World`, creating a new `Entity` for each of them.
+    /// References to saved entities are remapped for components registered with
+    /// `register_mapped`. Returns the map from saved to created entities. If any component cannot
+    /// be loaded or refers to an `Entity` that was not saved, no entities are created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Health(u32);
+    ///
+    /// components!(Health);
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register::<Health>("Health");
+    ///
+    ///     let mut world = World::new();
+    ///     world.register::<Health>();
+    ///     let json = r#"{ "entities": [
+    ///         { "id": 0, "tag": "Player", "components": { "Health": 100 } },
+    ///         { "id": 1, "components": { "Health": "full" } }
+    ///     ] }"#;
+    ///     assert!(registry.from_json(&mut world, json).is_err());
+    ///     assert!(world.filter(&ComponentFilter::new()).is_empty());
+    ///     assert_eq!(world.lookup("Player"), None);
+    /// }
+    /// ```
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Follow(Entity);
+    ///
+    /// impl MapEntities for Follow {
+    ///     fn map_entities(&mut self, map: &mut EntityMap) {
+    ///         self.0 = map.remap(self.0);
+    ///     }
+    /// }
+    ///
+    /// components!(Follow);
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register_mapped::<Follow>("Follow");
+    ///
+    ///     let mut world = World::new();
+    ///     world.register::<Follow>();
+    ///     let json = r#"{ "entities": [{ "id": 0, "tag": "Follower", "components": { "Follow": 7 } }] }"#;
+    ///     match registry.from_json(&mut world, json) {
+    ///         Err(SerializeError::DanglingEntities(dangling)) => assert_eq!(dangling, vec![(0, 7)]),
+    ///         _ => panic!("expected a dangling reference"),
+    ///     }
+    ///     assert!(world.filter(&ComponentFilter::new()).is_empty());
+    ///     assert_eq!(world.lookup("Follower"), None);
+    /// }
+    /// ```
+    pub fn load(&self, world: &mut World, saved: &SavedWorld) -> Result<EntityMap, SerializeError> {
+        for entity in &saved.entities {
+            for name in entity.components.keys() {
+                let entry = self.entry(name)?;
+                if !(entry.is_registered)(world) {
+                    return Err(SerializeError::Unregistered(name.clone()));
+                }
+            }
+        }
+
+        // The entities are created first so that references can be remapped as the components
+        // are deserialized. Nothing else is changed until every component has been loaded, so
+        // that an error only has to destroy the empty entities.
+        let mut map = EntityMap::new();
+        let mut created = Vec::new();
+        for entity in &saved.entities {
+            let entity_created = world.create();
+            map.insert(entity.id, entity_created);
+            created.push(entity_created);
+        }
+
+        let loaded = match self.load_components(saved, &mut map) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                for &entity in &created {
+                    world.destroy(entity);
+                }
+                return Err(err);
+            },
+        };
+
+        for ((entity, components), &created) in saved.entities.iter().zip(loaded).zip(&created) {
+            if let Some(ref tag) = entity.tag {
+                world.tag(created, tag);
+            }
+            for attach in components {
+                attach(world, created);
+            }
+        }
+        Ok(map)
+    }
+
+    fn load_components(&self, saved: &SavedWorld, map: &mut EntityMap) -> Result<Vec<Vec<AttachFn>>, SerializeError> {
+        let mut loaded = Vec::new();
+        for entity in &saved.entities {
+            let mut components = Vec::new();
+            for (name, value) in &entity.components {
+                let entry = self.entry(name)?;
+                components.push((entry.load)(value.clone(), Some(&mut *map))?);
+            }
+            map.attribute(entity.id);
+            loaded.push(components);
+        }
+
+        if map.dangling().is_empty() {
+            Ok(loaded)
+        } else {
+            Err(SerializeError::DanglingEntities(map.dangling().to_vec()))
+        }
+    }
+
+    /// Save the `World` as JSON.
+    pub fn to_json(&self, world: &World) -> Result<String, SerializeError> {
+        let saved = self.save(world)?;
+        Ok(serde_json::to_string_pretty(&saved)?)
+    }
+
+    /// Load entities from JSON into the `World`.
+    pub fn from_json(&self, world: &mut World, json: &str) -> Result<EntityMap, SerializeError> {
+        let saved = serde_json::from_str::<SavedWorld>(json)?;
+        self.load(world, &saved)
+    }
+
+    /// Save the `World` as RON.
+    pub fn to_ron(&self, world: &World) -> Result<String, SerializeError> {
+        let saved = self.save(world)?;
+        Ok(ron::ser::to_string_pretty(&saved, ron::ser::PrettyConfig::default())?)
+    }
+
+    /// Load entities from RON into the `World`.
+    pub fn from_ron(&self, world: &mut World, text: &str) -> Result<EntityMap, SerializeError> {
+        let saved = ron::de::from_str::<SavedWorld>(text)?;
+        self.load(world, &saved)
+    }
+
+    /// Capture the exact state of the `World`, the `EventQueue` and the `EventEmitter`.
+    pub(crate) fn take_snapshot(&self, world: &World, queue: &EventQueue, emitter: &EventEmitter)
+        -> Result<Snapshot, SerializeError> {
+        let entities = world.filter(&ComponentFilter::new());
+
+        let tags = entities.iter()
+            .filter_map(|&entity| world.tag_of(entity).map(|tag| (entity, tag.to_owned())))
+            .collect::<Vec<_>>();
+
+        let mut components = Vec::new();
+        for entry in &self.components {
+            let mut encoded = Vec::new();
+            for &entity in &entities {
+                if let Some(bytes) = (entry.encode)(world, entity) {
+                    encoded.push((entity, bytes?));
+                }
+            }
+            components.push((entry.name.clone(), encoded));
+        }
+
+        let mut events = Vec::new();
+        for entry in &self.events {
+            if !(entry.is_registered)(queue, emitter) {
+                return Err(SerializeError::UnregisteredEvent(entry.name.clone()));
+            }
+            events.push((entry.name.clone(), (entry.encode)(queue, emitter)?));
+        }
+
+        let mut resources = Vec::new();
+        for entry in &self.resources {
+            let encoded = match (entry.encode)(world) {
+                Some(bytes) => Some(bytes?),
+                None => None,
+            };
+            resources.push((entry.name.clone(), encoded));
+        }
+
+        Ok(Snapshot {
+            ids: world.ids().clone(),
+            tags,
+            components,
+            events,
+            resources,
+            time: world.resource::<Time>().cloned().unwrap_or_default(),
+            halt: None,
+            paused: false,
+            restart: false,
+        })
+    }
+
+    /// Encode the events that are waiting in the `EventEmitter`, keyed by their registered names.
+    pub(crate) fn record_events(&self, emitter: &EventEmitter) -> Result<RecordedEvents, SerializeError> {
+        let mut recorded = Vec::new();
+        for entry in &self.events {
+            let events = (entry.record)(emitter)?;
+            if !events.is_empty() {
+                recorded.push((entry.name.clone(), events));
+            }
+        }
+        Ok(recorded)
+    }
+
+    /// Emit events that were encoded by `record_events`.
+    pub(crate) fn replay_events(&self, emitter: &mut EventEmitter, recorded: &RecordedEvents)
+        -> Result<(), SerializeError> {
+        for (name, events) in recorded {
+            let entry = self.event_entry(name)?;
+            (entry.replay)(emitter, events)?;
+        }
+        Ok(())
+    }
+
+    /// Return the `World`, the `EventQueue` and the `EventEmitter` to the state captured in the
+    /// snapshot.
+    pub(crate) fn apply_snapshot(&self, snapshot: &Snapshot, world: &mut World, queue: &mut EventQueue,
+                                 emitter: &mut EventEmitter) -> Result<(), SerializeError> {
+        for (name, _) in &snapshot.components {
+            let entry = self.entry(name)?;
+            if !(entry.is_registered)(world) {
+                return Err(SerializeError::Unregistered(name.clone()));
+            }
+        }
+
+        for (name, _) in &snapshot.events {
+            let entry = self.event_entry(name)?;
+            if !(entry.is_registered)(queue, emitter) {
+                return Err(SerializeError::UnregisteredEvent(name.clone()));
+            }
+        }
+
+        let mut components = Vec::new();
+        for (name, encoded) in &snapshot.components {
+            let entry = self.entry(name)?;
+            for &(entity, ref bytes) in encoded {
+                components.push((entity, (entry.decode)(bytes)?));
+            }
+        }
+
+        let mut events = Vec::new();
+        for (name, encoded) in &snapshot.events {
+            let entry = self.event_entry(name)?;
+            events.push((entry.decode)(encoded)?);
+        }
+
+        let mut resources = Vec::new();
+        for (name, encoded) in &snapshot.resources {
+            let entry = self.resource_entry(name)?;
+            resources.push((entry.decode)(encoded.as_ref().map(Vec::as_slice))?);
+        }
+
+        world.reset(snapshot.ids.clone());
+        for &(entity, ref tag) in &snapshot.tags {
+            world.tag(entity, tag);
+        }
+        for (entity, attach) in components {
+            attach(world, entity);
+        }
+        for restore in resources {
+            restore(world);
+        }
+        if let Some(time) = world.resource_mut::<Time>() {
+            *time = snapshot.time;
+        }
+
+        emitter.clear();
+        for restore in events {
+            restore(queue, emitter);
+        }
+
+        Ok(())
+    }
+}
+
+type EncodedComponents = Vec<(Entity, Vec<u8>)>;
+
+/// Encoded events keyed by the names of their types.
+pub(crate) type RecordedEvents = Vec<(String, Vec<Vec<u8>>)>;
+
+#[derive(Serialize, Deserialize)]
+struct EncodedEvents {
+    retained: Vec<Vec<u8>>,
+    state: QueueState,
+    pending: Vec<Vec<u8>>,
+}
+
+/// The state of a `Simulation` captured by `Simulation::snapshot`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    ids: IdPool,
+    tags: Vec<(Entity, String)>,
+    components: Vec<(String, EncodedComponents)>,
+    events: Vec<(String, EncodedEvents)>,
+    resources: Vec<(String, Option<Vec<u8>>)>,
+    time: Time,
+    pub halt: Option<Halt>,
+    pub paused: bool,
+    pub restart: bool,
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializeError> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SerializeError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+impl Default for TypeRegistry {
+    fn default() -> TypeRegistry {
+        TypeRegistry::new()
+    }
+}
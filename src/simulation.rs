@@ -1,63 +1,1376 @@
-use super::family::{Family, FamilyMember};
+use std::any::type_name;
+use std::mem;
+use std::time::Instant;
+
 use super::world::World;
-use super::event::{EventQueue, EventEmitter};
-use super::system::System;
+use super::event::{Event, EventEmitter, EventInbox, EventPolicy, EventQueue, EventSender, Reader, TargetedEvent};
+use super::builder::{BuildError, SimulationBuilder};
+use super::condition::{Every, RunCondition};
+use super::state::{AnyStateDriver, NextState, State, StateDriver};
+use super::param::{Access, ParamFunction};
+use super::profile::Profile;
+use super::system::{FnSystem, ParamSystem, Requirement, Requirements, System, SystemId};
+use super::time::{calc_millis, Time};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+#[cfg(feature = "serde")]
+use super::record::{Recorder, Recording};
+#[cfg(feature = "serde")]
+use super::serialize::{Snapshot, SerializeError, TypeRegistry};
 
 /// Internal event used to stop the `Simulation`. This event is automatically
 /// registered.
-pub struct Halt;
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Halt {
+    /// The exit code of the program, where `0` indicates success.
+    pub code: i32,
+    /// Why the `Simulation` was stopped.
+    pub reason: String,
+}
+
+impl Halt {
+    /// Create a new `Halt` event.
+    pub fn new(code: i32, reason: &str) -> Halt {
+        Halt {
+            code,
+            reason: reason.to_owned(),
+        }
+    }
+
+    /// Stop the `Simulation` with an exit code of `0`.
+    pub fn success() -> Halt {
+        Halt::new(0, "")
+    }
+
+    /// Stop the `Simulation` with an exit code of `1`.
+    pub fn failure(reason: &str) -> Halt {
+        Halt::new(1, reason)
+    }
+}
+
+impl Event for Halt {}
+
+/// Internal event used to pause the `Simulation`. While paused, only the systems registered with
+/// `Simulation::register_unpausable` are updated. This event is automatically registered.
+pub struct Pause;
+
+impl Event for Pause {}
+
+/// Internal event used to resume a paused `Simulation`. If both `Pause` and `Resume` are emitted
+/// during the same step, the `Simulation` is resumed. This event is automatically registered.
+pub struct Resume;
+
+impl Event for Resume {}
+
+/// Internal event used to ask the owner of the `Simulation` to start it again. This event is
+/// automatically registered.
+pub struct Restart;
+
+impl Event for Restart {}
+
+// Systems that run when a state is left, then those that run when a state is entered, and then
+// every other system.
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Exit,
+    Enter,
+    Update,
+}
 
-impl FamilyMember for Halt {
-    fn family() -> Family {
-        0
+struct SystemEntry {
+    id: SystemId,
+    name: &'static str,
+    system: Box<dyn System>,
+    enabled: bool,
+    unpausable: bool,
+    stage: Stage,
+    conditions: Vec<Box<dyn RunCondition>>,
+}
+
+impl SystemEntry {
+    fn new<T: 'static + System>(system: T) -> SystemEntry {
+        SystemEntry::named(type_name::<T>(), system)
+    }
+
+    fn named<T: 'static + System>(name: &'static str, system: T) -> SystemEntry {
+        SystemEntry {
+            id: SystemId(0),
+            name,
+            system: Box::new(system),
+            enabled: true,
+            unpausable: false,
+            stage: Stage::Update,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn is_active(&mut self, world: &World, queue: &EventQueue, paused: bool, dt: f32) -> bool {
+        if !self.enabled || (paused && !self.unpausable) {
+            return false;
+        }
+
+        let mut active = true;
+        for condition in &mut self.conditions {
+            active &= condition.check(world, queue, dt);
+        }
+        active && self.system.should_run(world, queue, dt)
     }
 }
 
+type ReportHandler = Box<dyn FnMut(&Profile)>;
+
+// Changes to the registered systems that are made between the systems of a step.
+enum SystemChange {
+    Remove(SystemId),
+    Enable(SystemId, bool),
+    Replace(SystemId, Box<dyn System>, &'static str),
+}
+
+// The `Simulation` receives events as reader 0, the owner of the `Simulation` as reader 1, and
+// each `System` as its id plus two.
+const SIMULATION_READER: Reader = 0;
+const HOST_READER: Reader = 1;
+const FIRST_SYSTEM_READER: Reader = 2;
+
 /// Responsible for updating and passing events between systems.
 pub struct Simulation {
     world: World,
     queue: EventQueue,
     emitter: EventEmitter,
-    systems: Vec<Box<System>>,
-    halt: bool,
+    inbox: EventInbox,
+    undelivered: Vec<&'static str>,
+    systems: Vec<SystemEntry>,
+    next_system: usize,
+    changes: Vec<SystemChange>,
+    states: Vec<Box<dyn AnyStateDriver>>,
+    halt: Option<Halt>,
+    paused: bool,
+    restart: bool,
+    profile: Option<Profile>,
+    report: Option<(Every, ReportHandler)>,
+    #[cfg(feature = "serde")]
+    recorder: Option<Recorder>,
 }
 
 impl Simulation {
     /// Create a new `Simulation`.
-    pub fn new(world: World, mut queue: EventQueue, mut emitter: EventEmitter) -> Simulation {
+    #[deprecated(note = "use `Simulation::builder` instead")]
+    pub fn new(world: World, queue: EventQueue, emitter: EventEmitter) -> Simulation {
+        Simulation::from_parts(world, queue, emitter)
+    }
+
+    pub(crate) fn from_parts(mut world: World, mut queue: EventQueue, mut emitter: EventEmitter) -> Simulation {
         queue.register::<Halt>();
         emitter.register::<Halt>();
+        queue.register::<Pause>();
+        emitter.register::<Pause>();
+        queue.register::<Resume>();
+        emitter.register::<Resume>();
+        queue.register::<Restart>();
+        emitter.register::<Restart>();
+        world.add_resource(Time::default());
+        queue.read_as(HOST_READER);
 
         Simulation {
-            world: world,
-            queue: queue,
-            emitter: emitter,
+            world,
+            queue,
+            emitter,
+            inbox: EventInbox::new(),
+            undelivered: Vec::new(),
             systems: Vec::new(),
-            halt: false,
+            next_system: 0,
+            changes: Vec::new(),
+            states: Vec::new(),
+            halt: None,
+            paused: false,
+            restart: false,
+            profile: None,
+            report: None,
+            #[cfg(feature = "serde")]
+            recorder: None,
+        }
+    }
+
+    /// Create a `SimulationBuilder`.
+    pub fn builder() -> SimulationBuilder {
+        SimulationBuilder::new()
+    }
+
+    /// Register a new event type with both the `EventQueue` and the `EventEmitter`.
+    pub fn add_event<T: Event>(&mut self) {
+        self.queue.register::<T>();
+        self.emitter.register::<T>();
+    }
+
+    /// Register a new event type with both the `EventQueue` and the `EventEmitter`. The events
+    /// are received according to the given policy.
+    pub fn add_event_with<T: Event>(&mut self, policy: EventPolicy<T>) {
+        self.queue.register_with(policy);
+        self.emitter.register::<T>();
+    }
+
+    /// Register a new event type that is addressed to entities with both the `EventQueue` and the
+    /// `EventEmitter`.
+    pub fn add_targeted_event<T: TargetedEvent>(&mut self) {
+        self.queue.register_targeted::<T>();
+        self.emitter.register_targeted::<T>();
+    }
+
+    /// Call `handler` as soon as an event of the given type is emitted, rather than waiting for
+    /// the event to be merged into the `EventQueue`. The event is still queued as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use std::rc::Rc;
+    /// use std::cell::RefCell;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Sound(&'static str);
+    ///
+    /// events!(Sound);
+    ///
+    /// pub struct DoorSystem;
+    ///
+    /// impl System for DoorSystem {
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+    ///         emitter.emit(Sound("creak"));
+    ///         emitter.emit(Sound("slam"));
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let played = Rc::new(RefCell::new(Vec::new()));
+    ///
+    ///     let mut simulation = Simulation::builder().build().unwrap();
+    ///     simulation.add_event::<Sound>();
+    ///     let log = played.clone();
+    ///     simulation.subscribe(move |&Sound(name): &Sound| log.borrow_mut().push(name));
+    ///     simulation.register(DoorSystem);
+    ///
+    ///     simulation.update(16.0);
+    ///     assert_eq!(*played.borrow(), vec!["creak", "slam"]);
+    /// }
+    /// ```
+    pub fn subscribe<T: Event, F: 'static + FnMut(&T)>(&mut self, handler: F) {
+        self.emitter.subscribe(handler);
+    }
+
+    /// Create a handle that other threads can use to send events to the `Simulation`.
+    pub fn sender(&self) -> EventSender {
+        self.inbox.sender()
+    }
+
+    /// The type names of the events sent through an `EventSender` that were dropped at the start
+    /// of the last update, because their types are not registered.
+    pub fn undelivered(&self) -> &[&'static str] {
+        &self.undelivered
+    }
+
+    /// Register a `System` and return its id, which can be used to remove, replace, enable or
+    /// disable it.
+    pub fn register<T: 'static + System>(&mut self, system: T) -> SystemId {
+        self.push(SystemEntry::new(system))
+    }
+
+    /// Register a closure as a `System`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use trex::*;
+    ///
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// simulation.register_fn(|_world, _queue, emitter, dt| {
+    ///     if dt > 100.0 {
+    ///         emitter.emit(Halt::failure("too slow"));
+    ///     }
+    /// });
+    /// simulation.update(250.0);
+    /// assert_eq!(simulation.halt_status().unwrap().reason, "too slow");
+    /// ```
+    pub fn register_fn<F>(&mut self, func: F) -> SystemId
+        where F: 'static + FnMut(&mut World, &EventQueue, &mut EventEmitter, f32) {
+        self.push(SystemEntry::named(type_name::<F>(), FnSystem::new(func)))
+    }
+
+    /// Register a function as a `System`, passing it the parameters it asks for, such as a
+    /// `Query`, a `Res` or `ResMut`, the `EventQueue` or the `EventEmitter`. The time since the
+    /// last update is available as the `Time` resource.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters borrow the same component, resource or `EventEmitter` mutably
+    /// more than once, or both mutably and immutably, or if a component class or resource in the
+    /// parameters has not been registered.
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// use trex::*;
+    ///
+    /// struct Score(u32);
+    ///
+    /// fn count(mut score: ResMut<Score>) {
+    ///     score.0 += 1;
+    /// }
+    ///
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// simulation.register_param_fn(count);
+    /// ```
+    pub fn register_param_fn<P: 'static, F: 'static + ParamFunction<P>>(&mut self, func: F) -> SystemId {
+        let mut access = Access::new();
+        F::access(&mut access);
+        if let Some((requirement, name)) = self.unmet(&access.into_requirements()) {
+            panic!("{}", BuildError::Unmet {
+                system: type_name::<F>().to_owned(),
+                requirement,
+                name: name.to_owned(),
+            });
+        }
+        self.push_param_fn(func)
+    }
+
+    /// Register a function as a `System` without checking its requirements, which the
+    /// `SimulationBuilder` checks once every state has been added.
+    pub(crate) fn push_param_fn<P: 'static, F: 'static + ParamFunction<P>>(&mut self, func: F) -> SystemId {
+        self.push(SystemEntry::named(type_name::<F>(), ParamSystem::new(func)))
+    }
+
+    /// Register a `System` that only runs when the given condition is met.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct AutosaveSystem(Rc<Cell<usize>>);
+    ///
+    /// impl System for AutosaveSystem {
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {
+    ///         self.0.set(self.0.get() + 1);
+    ///     }
+    /// }
+    ///
+    /// let saves = Rc::new(Cell::new(0));
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// simulation.register_if(Every::millis(1000.0), AutosaveSystem(saves.clone()));
+    /// for _ in 0..5 {
+    ///     simulation.update(500.0);
+    /// }
+    /// assert_eq!(saves.get(), 2);
+    /// ```
+    pub fn register_if<C: 'static + RunCondition, T: 'static + System>(&mut self, condition: C, system: T) -> SystemId {
+        let mut entry = SystemEntry::new(system);
+        entry.conditions.push(Box::new(condition));
+        self.push(entry)
+    }
+
+    /// Register a `System` that keeps being updated while the `Simulation` is paused, such as one
+    /// that handles input or draws a menu.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use trex::*;
+    ///
+    /// pub struct CountSystem(usize);
+    ///
+    /// impl System for CountSystem {
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+    ///         self.0 += 1;
+    ///         if self.0 == 2 {
+    ///             emitter.emit(Halt::new(self.0 as i32, "counted twice"));
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// pub struct MenuSystem;
+    ///
+    /// impl System for MenuSystem {
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+    ///         emitter.emit(Resume);
+    ///     }
+    /// }
+    ///
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// simulation.register(CountSystem(0));
+    /// simulation.register_unpausable(MenuSystem);
+    ///
+    /// simulation.pause();
+    /// simulation.update(16.0);
+    /// assert!(!simulation.is_paused());
+    /// simulation.update(16.0);
+    /// simulation.update(16.0);
+    /// assert_eq!(simulation.halt_status(), Some(&Halt::new(2, "counted twice")));
+    /// ```
+    pub fn register_unpausable<T: 'static + System>(&mut self, system: T) -> SystemId {
+        let mut entry = SystemEntry::new(system);
+        entry.unpausable = true;
+        self.push(entry)
+    }
+
+    /// Add a `State` resource to the `World` that starts in the given state, and register the
+    /// `NextState` event that changes it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use trex::*;
+    ///
+    /// #[derive(Clone, PartialEq, Debug)]
+    /// pub enum Mode { Menu, Playing }
+    ///
+    /// pub struct MenuSystem;
+    ///
+    /// impl System for MenuSystem {
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+    ///         emitter.emit(NextState(Mode::Playing));
+    ///     }
+    /// }
+    ///
+    /// pub struct StartSystem;
+    ///
+    /// impl System for StartSystem {
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+    ///         emitter.emit(Halt::success());
+    ///     }
+    /// }
+    ///
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// simulation.add_state(Mode::Menu);
+    /// simulation.register_in_state(Mode::Menu, MenuSystem);
+    /// simulation.register_on_enter(Mode::Playing, StartSystem);
+    ///
+    /// simulation.update(16.0);
+    /// assert!(!simulation.halt());
+    /// simulation.update(16.0);
+    /// assert!(simulation.halt());
+    /// ```
+    pub fn add_state<S: 'static + Clone + PartialEq>(&mut self, initial: S) {
+        self.world.add_resource(State::new(initial));
+        self.add_event::<NextState<S>>();
+        self.states.push(Box::new(StateDriver::<S>::new()));
+    }
+
+    /// Register a `System` that only runs while the `State` resource is in the given state.
+    pub fn register_in_state<S, T>(&mut self, state: S, system: T) -> SystemId
+        where S: 'static + Clone + PartialEq, T: 'static + System {
+        let mut entry = SystemEntry::new(system);
+        entry.conditions.push(Box::new(move |world: &World, _: &EventQueue, _: f32| {
+            world.resource::<State<S>>().map(State::current) == Some(&state)
+        }));
+        self.push(entry)
+    }
+
+    /// Register a `System` that runs once, in the step in which the given state is entered.
+    /// Systems that run when entering a state run after those that run when leaving a state, and
+    /// before every other system.
+    pub fn register_on_enter<S, T>(&mut self, state: S, system: T) -> SystemId
+        where S: 'static + Clone + PartialEq, T: 'static + System {
+        let mut entry = SystemEntry::new(system);
+        entry.stage = Stage::Enter;
+        entry.conditions.push(Box::new(move |world: &World, _: &EventQueue, _: f32| {
+            world.resource::<State<S>>().and_then(State::entered) == Some(&state)
+        }));
+        self.push(entry)
+    }
+
+    /// Register a `System` that runs once, in the step in which the given state is left. Systems
+    /// that run when leaving a state run before every other system.
+    pub fn register_on_exit<S, T>(&mut self, state: S, system: T) -> SystemId
+        where S: 'static + Clone + PartialEq, T: 'static + System {
+        let mut entry = SystemEntry::new(system);
+        entry.stage = Stage::Exit;
+        entry.conditions.push(Box::new(move |world: &World, _: &EventQueue, _: f32| {
+            world.resource::<State<S>>().and_then(State::exited) == Some(&state)
+        }));
+        self.push(entry)
+    }
+
+    /// Remove a `System`, calling its `shutdown` method, at the start of the next update. Returns
+    /// `false` if no `System` has the given id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// use trex::*;
+    ///
+    /// let ticks = Rc::new(Cell::new(0));
+    /// let counter = ticks.clone();
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// let id = simulation.register_fn(move |_world, _queue, _emitter, _dt| counter.set(counter.get() + 1));
+    ///
+    /// simulation.update(16.0);
+    /// assert!(simulation.set_enabled(id, false));
+    /// simulation.update(16.0);
+    /// assert_eq!(ticks.get(), 1);
+    ///
+    /// simulation.set_enabled(id, true);
+    /// simulation.update(16.0);
+    /// assert_eq!(ticks.get(), 2);
+    ///
+    /// assert!(simulation.remove_system(id));
+    /// simulation.update(16.0);
+    /// assert_eq!(ticks.get(), 2);
+    /// assert!(!simulation.remove_system(id));
+    /// ```
+    pub fn remove_system(&mut self, id: SystemId) -> bool {
+        self.change(id, SystemChange::Remove(id))
+    }
+
+    /// Enable or disable a `System` at the start of the next update. A disabled `System` is
+    /// skipped, and does not receive the events emitted while it is disabled. Returns `false` if
+    /// no `System` has the given id.
+    pub fn set_enabled(&mut self, id: SystemId, enabled: bool) -> bool {
+        self.change(id, SystemChange::Enable(id, enabled))
+    }
+
+    /// Returns `true` if the `System` with the given id exists and is enabled, otherwise `false`.
+    pub fn is_enabled(&self, id: SystemId) -> bool {
+        self.systems.iter().any(|entry| entry.id == id && entry.enabled)
+    }
+
+    /// Whether the `System` will be enabled once the pending changes have been applied.
+    pub(crate) fn will_be_enabled(&self, id: SystemId) -> bool {
+        let mut enabled = self.is_enabled(id);
+        for change in &self.changes {
+            match *change {
+                SystemChange::Enable(other, value) if other == id => enabled = value,
+                SystemChange::Remove(other) if other == id => enabled = false,
+                _ => {},
+            }
         }
+        enabled
     }
 
-    /// Register a `System`.
-    pub fn register<T: 'static + System>(&mut self, system: T) {
-        self.systems.push(Box::new(system));
+    /// Replace a `System` at the start of the next update. The `shutdown` method of the old
+    /// `System` is called, followed by the `init` method of the new one. The new `System` keeps
+    /// the id, conditions and position of the old one, and receives the events that the old one
+    /// had not yet received. Returns `false` if no `System` has the given id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Word(pub &'static str);
+    ///
+    /// events!(Word);
+    ///
+    /// pub struct Speaker {
+    ///     pub name: &'static str,
+    ///     pub log: Rc<RefCell<Vec<String>>>,
+    /// }
+    ///
+    /// impl System for Speaker {
+    ///     fn init(&mut self, _world: &mut World, _emitter: &mut EventEmitter) {
+    ///         self.log.borrow_mut().push(format!("{} init", self.name));
+    ///     }
+    ///
+    ///     fn shutdown(&mut self, _world: &mut World) {
+    ///         self.log.borrow_mut().push(format!("{} shutdown", self.name));
+    ///     }
+    ///
+    ///     fn update(&mut self, _world: &mut World, queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {
+    ///         for word in queue.receive::<Word>() {
+    ///             self.log.borrow_mut().push(format!("{} heard {}", self.name, word.0));
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let log = Rc::new(RefCell::new(Vec::new()));
+    ///     let mut builder = Simulation::builder();
+    ///     builder.add_event::<Word>();
+    ///     let mut simulation = builder.build().unwrap();
+    ///     let id = simulation.register(Speaker { name: "old", log: log.clone() });
+    ///     simulation.update(16.0);
+    ///
+    ///     simulation.emit(Word("hello"));
+    ///     assert!(simulation.replace_system(id, Speaker { name: "new", log: log.clone() }));
+    ///     simulation.update(16.0);
+    ///     simulation.update(16.0);
+    ///
+    ///     assert_eq!(*log.borrow(), vec!["old init", "old shutdown", "new init", "new heard hello"]);
+    ///     assert_eq!(simulation.systems()[0].0, id);
+    /// }
+    /// ```
+    pub fn replace_system<T: 'static + System>(&mut self, id: SystemId, system: T) -> bool {
+        self.change(id, SystemChange::Replace(id, Box::new(system), type_name::<T>()))
+    }
+
+    /// The id and type name of every registered `System`, in the order in which they run within
+    /// their stage.
+    pub fn systems(&self) -> Vec<(SystemId, &'static str)> {
+        self.systems.iter().map(|entry| (entry.id, entry.name)).collect()
+    }
+
+    /// The id of the first registered `System` of the given type, such as one registered through
+    /// a `SimulationBuilder`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use trex::*;
+    ///
+    /// struct Idle;
+    ///
+    /// impl System for Idle {
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {}
+    /// }
+    ///
+    /// let mut builder = Simulation::builder();
+    /// builder.register(Idle);
+    /// let mut simulation = builder.build().unwrap();
+    ///
+    /// let id = simulation.system_id::<Idle>().unwrap();
+    /// simulation.set_enabled(id, false);
+    /// simulation.update(16.0);
+    /// assert!(!simulation.is_enabled(id));
+    /// ```
+    pub fn system_id<T: 'static + System>(&self) -> Option<SystemId> {
+        let name = type_name::<T>();
+        self.systems.iter().find(|entry| entry.name == name).map(|entry| entry.id)
+    }
+
+    fn change(&mut self, id: SystemId, change: SystemChange) -> bool {
+        let exists = self.systems.iter().any(|entry| entry.id == id);
+        if exists {
+            self.changes.push(change);
+        }
+        exists
+    }
+
+    // Systems are only shut down once, so those that are removed or replaced after the
+    // `Simulation` halts are not shut down again.
+    fn apply_changes(&mut self) {
+        for change in mem::take(&mut self.changes) {
+            match change {
+                SystemChange::Remove(id) => {
+                    if let Some(index) = self.systems.iter().position(|entry| entry.id == id) {
+                        let mut entry = self.systems.remove(index);
+                        if let Some(ref mut profile) = self.profile {
+                            profile.forget(id);
+                        }
+                        if self.halt.is_none() {
+                            entry.system.shutdown(&mut self.world);
+                        }
+                    }
+                },
+                SystemChange::Enable(id, enabled) => {
+                    if let Some(entry) = self.systems.iter_mut().find(|entry| entry.id == id) {
+                        entry.enabled = enabled;
+                    }
+                },
+                SystemChange::Replace(id, mut system, name) => {
+                    if let Some(entry) = self.systems.iter_mut().find(|entry| entry.id == id) {
+                        // A halted `Simulation` initializes its systems when it is restored.
+                        if self.halt.is_none() {
+                            entry.system.shutdown(&mut self.world);
+                            system.init(&mut self.world, &mut self.emitter);
+                        }
+                        entry.system = system;
+                        if let Some(ref mut profile) = self.profile {
+                            profile.forget(id);
+                        }
+                        entry.name = name;
+                    }
+                },
+            }
+        }
+    }
+
+    /// The first of the requirements that is not met, if any.
+    pub(crate) fn unmet(&self, requirements: &Requirements) -> Option<(Requirement, &'static str)> {
+        requirements.unmet(&self.world, &self.queue, &self.emitter)
+    }
+
+    fn push(&mut self, mut entry: SystemEntry) -> SystemId {
+        let id = SystemId(self.next_system);
+        self.next_system += 1;
+        entry.id = id;
+        if self.halt.is_none() {
+            entry.system.init(&mut self.world, &mut self.emitter);
+        }
+        self.systems.push(entry);
+        id
+    }
+
+    /// The `World` of the `Simulation`.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// The `World` of the `Simulation`, which can be changed between updates.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// The events that were emitted during the last update, including those emitted with
+    /// `Simulation::emit` before it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct Health(pub u32);
+    ///
+    /// pub struct Heal(pub Entity);
+    ///
+    /// pub struct Healed(pub Entity);
+    ///
+    /// components!(Health);
+    /// events!(Heal, Healed);
+    ///
+    /// pub struct HealSystem;
+    ///
+    /// impl System for HealSystem {
+    ///     fn update(&mut self, world: &mut World, queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+    ///         for &Heal(entity) in queue.receive() {
+    ///             world.get_mut::<Health>(entity).unwrap().0 = 100;
+    ///             emitter.emit(Healed(entity));
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut builder = Simulation::builder();
+    ///     builder.add_component::<Health>()
+    ///         .add_event::<Heal>()
+    ///         .add_event::<Healed>()
+    ///         .register(HealSystem);
+    ///     let mut simulation = builder.build().unwrap();
+    ///
+    ///     let entity = simulation.world_mut().create();
+    ///     simulation.world_mut().add(entity, Health(10));
+    ///
+    ///     simulation.emit(Heal(entity));
+    ///     simulation.update(16.0);
+    ///     assert_eq!(simulation.world().get::<Health>(entity).unwrap().0, 100);
+    ///     assert_eq!(simulation.queue().receive::<Healed>().count(), 1);
+    ///
+    ///     simulation.update(16.0);
+    ///     assert_eq!(simulation.queue().receive::<Healed>().count(), 0);
+    /// }
+    /// ```
+    pub fn queue(&self) -> &EventQueue {
+        &self.queue
+    }
+
+    /// Emit an event from outside the systems. The event is received during the next update.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type has not been registered.
+    pub fn emit<T: Event>(&mut self, event: T) {
+        self.emitter.emit(event);
     }
 
     /// Returns `true` if the `Halt` event has been emitted, otherwise `false`.
     pub fn halt(&self) -> bool {
-        self.halt
+        self.halt.is_some()
+    }
+
+    /// The first `Halt` event that was emitted, if any.
+    pub fn halt_status(&self) -> Option<&Halt> {
+        self.halt.as_ref()
+    }
+
+    /// Returns `true` if the `Restart` event has been emitted, otherwise `false`. The owner of the
+    /// `Simulation` is expected to replace it with a new one.
+    pub fn restart(&self) -> bool {
+        self.restart
+    }
+
+    /// Pause the `Simulation`. Systems that are not unpausable are skipped, and do not receive
+    /// the events emitted while they are paused.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resume a paused `Simulation`.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Returns `true` if the `Simulation` is paused, otherwise `false`.
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 
-    /// Perform a single simulation step.
+    /// Perform a single simulation step. Does nothing once the `Simulation` has halted, since
+    /// every `System` has been shut down.
+    ///
+    /// With the `tracing` feature, each step is traced as an `update` span that contains a
+    /// `system` span for each `System` that runs, along with spans for merging events and for
+    /// changes to the `World`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[cfg(feature = "tracing")]
+    /// extern crate tracing;
+    /// extern crate trex;
+    ///
+    /// #[cfg(feature = "tracing")]
+    /// fn main() {
+    ///     use std::sync::{Arc, Mutex};
+    ///
+    ///     use tracing::span::{Attributes, Id, Record};
+    ///     use tracing::{Event, Metadata, Subscriber};
+    ///
+    ///     // Records the name of each span that is created.
+    ///     struct Spans(Arc<Mutex<Vec<&'static str>>>);
+    ///
+    ///     impl Subscriber for Spans {
+    ///         fn enabled(&self, _metadata: &Metadata) -> bool { true }
+    ///         fn new_span(&self, span: &Attributes) -> Id {
+    ///             let mut names = self.0.lock().unwrap();
+    ///             names.push(span.metadata().name());
+    ///             Id::from_u64(names.len() as u64)
+    ///         }
+    ///         fn record(&self, _span: &Id, _values: &Record) {}
+    ///         fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+    ///         fn event(&self, _event: &Event) {}
+    ///         fn enter(&self, _span: &Id) {}
+    ///         fn exit(&self, _span: &Id) {}
+    ///     }
+    ///
+    ///     let names = Arc::new(Mutex::new(Vec::new()));
+    ///     let mut simulation = trex::Simulation::builder().build().unwrap();
+    ///     simulation.register_fn(|world, _queue, _emitter, _dt| { world.create(); });
+    ///     tracing::subscriber::with_default(Spans(names.clone()), || simulation.update(16.0));
+    ///     let expected = vec!["update", "merge_events", "system", "create", "merge_events"];
+    ///     assert_eq!(*names.lock().unwrap(), expected);
+    /// }
+    ///
+    /// #[cfg(not(feature = "tracing"))]
+    /// fn main() {}
+    /// ```
     pub fn update(&mut self, dt: f32) {
-        for system in &mut self.systems {
-            system.update(&mut self.world, &self.queue, &mut self.emitter, dt);
-            self.queue.merge(&mut self.emitter);
+        if self.halt.is_some() {
+            return;
+        }
+
+        span!(DEBUG, "update", dt);
+        let start = self.profile.as_ref().map(|_| Instant::now());
+        self.apply_changes();
+        self.undelivered = self.inbox.deliver(&mut self.emitter);
+
+        // The owner of the `Simulation` has had its chance to receive the events of the last step.
+        self.queue.read_as(HOST_READER);
+        self.queue.advance();
+
+        if let Some(time) = self.world.resource_mut::<Time>() {
+            time.delta = dt;
+            time.elapsed += dt;
         }
 
-        if let Some(&Halt) = self.queue.receive::<Halt>().next() {
-            self.halt = true;
+        #[cfg(feature = "serde")]
+        {
+            if let Some(ref mut recorder) = self.recorder {
+                recorder.record(&self.emitter, dt);
+            }
+        }
+
+        self.queue.merge(&mut self.emitter);
+
+        for state in &self.states {
+            state.apply(&mut self.world);
         }
 
+        for &stage in &[Stage::Exit, Stage::Enter, Stage::Update] {
+            for entry in &mut self.systems {
+                if entry.stage != stage {
+                    continue;
+                }
+
+                self.queue.read_as(entry.id.0 + FIRST_SYSTEM_READER);
+                if entry.is_active(&self.world, &self.queue, self.paused, dt) {
+                    span!(DEBUG, "system", name = entry.name, id = entry.id.0);
+                    match self.profile {
+                        Some(ref mut profile) => {
+                            let entities = self.world.filtered();
+                            let events = self.emitter.emitted();
+                            let start = Instant::now();
+                            entry.system.update(&mut self.world, &self.queue, &mut self.emitter, dt);
+                            profile.record_system(entry.id, entry.name, calc_millis(start.elapsed()),
+                                self.world.filtered() - entities, self.emitter.emitted() - events);
+                        },
+                        None => entry.system.update(&mut self.world, &self.queue, &mut self.emitter, dt),
+                    }
+                }
+                self.queue.advance();
+                self.queue.merge(&mut self.emitter);
+            }
+        }
+
+        self.queue.read_as(SIMULATION_READER);
+        if self.halt.is_none() {
+            self.halt = self.queue.receive::<Halt>().next().cloned();
+            if self.halt.is_some() {
+                for entry in self.systems.iter_mut().rev() {
+                    entry.system.shutdown(&mut self.world);
+                }
+            }
+        }
+        if self.queue.receive::<Pause>().next().is_some() {
+            self.paused = true;
+        }
+        if self.queue.receive::<Resume>().next().is_some() {
+            self.paused = false;
+        }
+        if self.queue.receive::<Restart>().next().is_some() {
+            self.restart = true;
+        }
+        for state in &self.states {
+            state.finish(&mut self.world, &self.queue);
+        }
+        self.queue.advance();
+
         self.queue.flush();
+        self.queue.read_as(HOST_READER);
+
+        if let (Some(start), Some(profile)) = (start, self.profile.as_mut()) {
+            profile.record_frame(calc_millis(start.elapsed()));
+            if let Some((ref mut every, ref mut handler)) = self.report {
+                if every.check(&self.world, &self.queue, dt) {
+                    handler(profile);
+                }
+            }
+        }
+    }
+
+    /// Start measuring the wall time of each update and of each `System`, along with the number
+    /// of entities each `System` filters and the number of events it emits. The statistics cover
+    /// the last `window` samples of each measurement. Any earlier measurements are discarded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use trex::*;
+    ///
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// for _ in 0..3 {
+    ///     simulation.world_mut().create();
+    /// }
+    /// let id = simulation.register_fn(|world, _queue, _emitter, _dt| {
+    ///     world.filter(&ComponentFilter::new());
+    /// });
+    /// simulation.start_profiling(60);
+    /// simulation.report_profile_every(1000.0, |profile| print!("{}", profile));
+    /// for _ in 0..100 {
+    ///     simulation.update(16.0);
+    /// }
+    ///
+    /// let profile = simulation.profile().unwrap();
+    /// assert_eq!(profile.frame().len(), 60);
+    /// let system = profile.system(id).unwrap();
+    /// assert_eq!(system.entities().stats().avg, 3.0);
+    /// assert!(system.time().stats().max <= profile.frame().stats().max);
+    /// ```
+    pub fn start_profiling(&mut self, window: usize) {
+        self.profile = Some(Profile::new(window));
+    }
+
+    /// Stop profiling and return the measurements, if the `Simulation` was being profiled.
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// The measurements taken since profiling started, if the `Simulation` is being profiled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// While the `Simulation` is being profiled, call `handler` with the measurements each time
+    /// the given number of milliseconds has been simulated. Formatting the `Profile` gives a text
+    /// report. Replaces any earlier handler.
+    pub fn report_profile_every<F: 'static + FnMut(&Profile)>(&mut self, interval: f32, handler: F) {
+        self.report = Some((Every::millis(interval), Box::new(handler)));
+    }
+
+    /// Capture the state of the `World` and any events that have not yet been received as a
+    /// compact binary blob. The `Time` resource is always included, and otherwise only the
+    /// component classes, event types and resources in the registry. Returns
+    /// `SerializeError::UnregisteredEvent` if the registry has an event type that has not been
+    /// registered with the `Simulation`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Health(u32);
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Heal(u32);
+    ///
+    /// components!(Health);
+    /// events!(Heal);
+    ///
+    /// pub struct SpawnSystem;
+    ///
+    /// impl System for SpawnSystem {
+    ///     fn update(&mut self, world: &mut World, _queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {
+    ///         world.destroy(0);
+    ///         let entity = world.create();
+    ///         world.add(entity, Health(10));
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register::<Health>("Health");
+    ///
+    ///     let mut builder = Simulation::builder();
+    ///     let world = builder.add_component::<Health>().world_mut();
+    ///     let entity = world.create();
+    ///     world.add(entity, Health(100));
+    ///     builder.register(SpawnSystem);
+    ///     let mut simulation = builder.build().unwrap();
+    ///
+    ///     let snapshot = simulation.snapshot(&registry).unwrap();
+    ///     simulation.update(16.0);
+    ///     assert!(simulation.snapshot(&registry).unwrap() != snapshot);
+    ///
+    ///     simulation.restore(&registry, &snapshot).unwrap();
+    ///     assert_eq!(simulation.snapshot(&registry).unwrap(), snapshot);
+    ///
+    ///     registry.register_event::<Heal>("Heal");
+    ///     match simulation.snapshot(&registry) {
+    ///         Err(SerializeError::UnregisteredEvent(name)) => assert_eq!(name, "Heal"),
+    ///         _ => panic!("`Heal` has not been registered with the simulation"),
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self, registry: &TypeRegistry) -> Result<Vec<u8>, SerializeError> {
+        let mut snapshot = registry.take_snapshot(&self.world, &self.queue, &self.emitter)?;
+        snapshot.halt = self.halt.clone();
+        snapshot.paused = self.paused;
+        snapshot.restart = self.restart;
+        snapshot.to_bytes()
+    }
+
+    /// Return the `Simulation` to the state captured by `snapshot`, including the order in which
+    /// entity ids are reused. Components that are not in the registry are removed. If the
+    /// snapshot cannot be decoded, the `Simulation` is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Health(u32);
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Name(String);
+    ///
+    /// components!(Health, Name);
+    ///
+    /// fn main() {
+    ///     let mut builder = Simulation::builder();
+    ///     let world = builder.add_component::<Health>().add_component::<Name>().world_mut();
+    ///     let entity = world.create();
+    ///     world.add(entity, Health(100));
+    ///     let mut simulation = builder.build().unwrap();
+    ///
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register::<Health>("Health");
+    ///     let snapshot = simulation.snapshot(&registry).unwrap();
+    ///
+    ///     // A registry that uses the same name for a different component class.
+    ///     let mut other = TypeRegistry::new();
+    ///     other.register::<Name>("Health");
+    ///     simulation.world_mut().get_mut::<Health>(entity).unwrap().0 = 50;
+    ///     assert!(simulation.restore(&other, &snapshot).is_err());
+    ///     assert_eq!(simulation.world().get::<Health>(entity).unwrap().0, 50);
+    /// }
+    /// ```
+    ///
+    /// Restoring a halted `Simulation` initializes its systems again, and the events they emit
+    /// are replaced by those in the snapshot.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Started;
+    ///
+    /// events!(Started);
+    ///
+    /// pub struct Starter(Rc<Cell<u32>>);
+    ///
+    /// impl System for Starter {
+    ///     fn init(&mut self, _world: &mut World, emitter: &mut EventEmitter) {
+    ///         self.0.set(self.0.get() + 1);
+    ///         emitter.emit(Started);
+    ///     }
+    ///
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {}
+    /// }
+    ///
+    /// fn main() {
+    ///     let inits = Rc::new(Cell::new(0));
+    ///     let mut builder = Simulation::builder();
+    ///     builder.add_event::<Started>();
+    ///     let mut simulation = builder.build().unwrap();
+    ///     let id = simulation.register(Starter(inits.clone()));
+    ///
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register_event::<Started>("Started");
+    ///     simulation.update(16.0);
+    ///     let snapshot = simulation.snapshot(&registry).unwrap();
+    ///
+    ///     simulation.emit(Halt::success());
+    ///     simulation.update(16.0);
+    ///     assert!(simulation.halt());
+    ///     simulation.replace_system(id, Starter(inits.clone()));
+    ///
+    ///     simulation.restore(&registry, &snapshot).unwrap();
+    ///     assert_eq!(inits.get(), 2);
+    ///     assert_eq!(simulation.snapshot(&registry).unwrap(), snapshot);
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn restore(&mut self, registry: &TypeRegistry, bytes: &[u8]) -> Result<(), SerializeError> {
+        let snapshot = Snapshot::from_bytes(bytes)?;
+
+        // Systems that were shut down when the `Simulation` halted are initialized again before
+        // the snapshot is applied, so that it replaces the events they emit. The changes made while
+        // halted are applied first, so that each `System` is initialized once.
+        let resume = self.halt.is_some() && snapshot.halt.is_none();
+        if resume {
+            self.apply_changes();
+            for entry in &mut self.systems {
+                entry.system.init(&mut self.world, &mut self.emitter);
+            }
+        }
+
+        if let Err(err) = registry.apply_snapshot(&snapshot, &mut self.world, &mut self.queue, &mut self.emitter) {
+            if resume {
+                for entry in self.systems.iter_mut().rev() {
+                    entry.system.shutdown(&mut self.world);
+                }
+            }
+            return Err(err);
+        }
+        self.halt = snapshot.halt;
+        self.paused = snapshot.paused;
+        self.restart = snapshot.restart;
+        Ok(())
+    }
+
+    /// Start recording the events that enter the `Simulation` from outside, along with the
+    /// length of each step, so that the session can be replayed later. Only the event types in
+    /// the registry are recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Score(u32);
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Points(u32);
+    ///
+    /// components!(Score);
+    /// events!(Points);
+    ///
+    /// pub struct ScoreSystem;
+    ///
+    /// impl System for ScoreSystem {
+    ///     fn update(&mut self, world: &mut World, queue: &EventQueue, _emitter: &mut EventEmitter, _dt: f32) {
+    ///         for &Points(points) in queue.receive() {
+    ///             world.get_mut::<Score>(0).unwrap().0 += points;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn build() -> Simulation {
+    ///     let mut builder = Simulation::builder();
+    ///     let world = builder.add_component::<Score>().world_mut();
+    ///     let entity = world.create();
+    ///     world.add(entity, Score(0));
+    ///     builder.add_event::<Points>().register(ScoreSystem);
+    ///     builder.build().unwrap()
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register::<Score>("Score");
+    ///     registry.register_event::<Points>("Points");
+    ///
+    ///     let mut simulation = build();
+    ///     simulation.start_recording(&registry).unwrap();
+    ///     let sender = simulation.sender();
+    ///     for points in 1..4 {
+    ///         sender.send(Points(points));
+    ///         simulation.update(16.0);
+    ///     }
+    ///     let recording = simulation.stop_recording().unwrap();
+    ///     assert_eq!(recording.len(), 3);
+    ///
+    ///     let recording = Recording::from_bytes(&recording.to_bytes().unwrap()).unwrap();
+    ///     let mut replayed = build();
+    ///     replayed.replay(&registry, &recording).unwrap();
+    ///     assert_eq!(replayed.world().get::<Score>(0).unwrap().0, 6);
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn start_recording(&mut self, registry: &TypeRegistry) -> Result<(), SerializeError> {
+        let start = self.snapshot(registry)?;
+        self.recorder = Some(Recorder::new(registry.clone(), start));
+        Ok(())
+    }
+
+    /// Returns `true` if the `Simulation` is being recorded, otherwise `false`.
+    #[cfg(feature = "serde")]
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Stop recording and return the `Recording`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Simulation` is not being recorded.
+    #[cfg(feature = "serde")]
+    pub fn stop_recording(&mut self) -> Result<Recording, SerializeError> {
+        let recorder = match self.recorder.take() {
+            Some(recorder) => recorder,
+            None => panic!("the simulation is not being recorded"),
+        };
+        let end = self.snapshot(recorder.registry())?;
+        recorder.finish(end)
+    }
+
+    /// Return to the state at the start of the `Recording` and perform each recorded step again,
+    /// emitting the recorded events before each one. Returns `SerializeError::Diverged` if the
+    /// final state does not match the recorded one.
+    ///
+    /// The `Simulation` must have the same systems and event types as the one that was recorded.
+    ///
+    /// # Examples
+    ///
+    /// Events that were emitted before recording started are replayed once.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    /// extern crate serde;
+    ///
+    /// use serde::{Serialize, Deserialize};
+    /// use trex::*;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Score(u32);
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// pub struct Points(u32);
+    ///
+    /// components!(Score);
+    /// events!(Points);
+    ///
+    /// fn build() -> Simulation {
+    ///     let mut builder = Simulation::builder();
+    ///     let world = builder.add_component::<Score>().world_mut();
+    ///     let entity = world.create();
+    ///     world.add(entity, Score(0));
+    ///     builder.add_event::<Points>().register_fn(|world, queue, _emitter, _dt| {
+    ///         for &Points(points) in queue.receive() {
+    ///             world.get_mut::<Score>(0).unwrap().0 += points;
+    ///         }
+    ///     });
+    ///     builder.build().unwrap()
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut registry = TypeRegistry::new();
+    ///     registry.register::<Score>("Score");
+    ///     registry.register_event::<Points>("Points");
+    ///
+    ///     let mut simulation = build();
+    ///     simulation.emit(Points(5));
+    ///     simulation.start_recording(&registry).unwrap();
+    ///     simulation.update(16.0);
+    ///     let recording = simulation.stop_recording().unwrap();
+    ///
+    ///     let mut replayed = build();
+    ///     replayed.replay(&registry, &recording).unwrap();
+    ///     assert_eq!(replayed.world().get::<Score>(0).unwrap().0, 5);
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn replay(&mut self, registry: &TypeRegistry, recording: &Recording) -> Result<(), SerializeError> {
+        self.restore(registry, &recording.start)?;
+        // The events that were waiting when recording started are recorded with the first step.
+        self.emitter.clear();
+
+        for frame in &recording.frames {
+            registry.replay_events(&mut self.emitter, &frame.events)?;
+            self.update(frame.dt);
+        }
+
+        if self.snapshot(registry)? == recording.end {
+            Ok(())
+        } else {
+            Err(SerializeError::Diverged)
+        }
     }
 }
@@ -0,0 +1,119 @@
+use std::marker::PhantomData;
+use std::mem;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use super::event::{Event, EventQueue};
+use super::world::World;
+
+/// A resource that holds the current state of a `Simulation`, such as whether the game is showing
+/// a menu or being played. Added to the `World` by `Simulation::add_state`.
+///
+/// Changes of state take effect at the start of the next simulation step. With the `serde` feature,
+/// a `State` can be included in snapshots with `TypeRegistry::register_resource`.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct State<S> {
+    current: S,
+    next: Option<S>,
+    entered: Option<S>,
+    exited: Option<S>,
+}
+
+impl<S: Clone + PartialEq> State<S> {
+    /// Create a new `State`. The initial state counts as entered during the first step.
+    pub(crate) fn new(initial: S) -> State<S> {
+        State {
+            current: initial.clone(),
+            next: None,
+            entered: Some(initial),
+            exited: None,
+        }
+    }
+
+    /// The current state.
+    pub fn current(&self) -> &S {
+        &self.current
+    }
+
+    /// Change to the given state at the start of the next step.
+    pub fn set(&mut self, next: S) {
+        self.next = Some(next);
+    }
+
+    /// The state that was entered at the start of this step, if any.
+    pub fn entered(&self) -> Option<&S> {
+        self.entered.as_ref()
+    }
+
+    /// The state that was left at the start of this step, if any.
+    pub fn exited(&self) -> Option<&S> {
+        self.exited.as_ref()
+    }
+
+    fn apply(&mut self) {
+        if let Some(next) = self.next.take() {
+            if next != self.current {
+                self.exited = Some(mem::replace(&mut self.current, next.clone()));
+                self.entered = Some(next);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.entered = None;
+        self.exited = None;
+    }
+}
+
+/// Event used to change the state of a `Simulation` at the start of the next step. This event is
+/// registered by `Simulation::add_state`. If it is emitted more than once during a step, the last
+/// one wins.
+pub struct NextState<S>(pub S);
+
+impl<S: 'static> Event for NextState<S> {}
+
+/// Performs the transitions of a single `State` resource on behalf of the `Simulation`.
+pub trait AnyStateDriver {
+    /// Change to the requested state, if any.
+    fn apply(&self, world: &mut World);
+    /// Forget the transition of this step and receive the requests for the next one.
+    fn finish(&self, world: &mut World, queue: &EventQueue);
+}
+
+pub struct StateDriver<S> {
+    _state: PhantomData<S>,
+}
+
+impl<S> StateDriver<S> {
+    pub fn new() -> StateDriver<S> {
+        StateDriver {
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<S: 'static + Clone + PartialEq> AnyStateDriver for StateDriver<S> {
+    // The `State` is only borrowed mutably when it changes, so that it does not count as changed
+    // in every step.
+    fn apply(&self, world: &mut World) {
+        let pending = world.resource::<State<S>>().is_some_and(|state| state.next.is_some());
+        if pending {
+            world.resource_mut::<State<S>>().unwrap().apply();
+        }
+    }
+
+    fn finish(&self, world: &mut World, queue: &EventQueue) {
+        let transition = world.resource::<State<S>>()
+            .is_some_and(|state| state.entered.is_some() || state.exited.is_some());
+        if transition {
+            world.resource_mut::<State<S>>().unwrap().finish();
+        }
+
+        if let Some(NextState(next)) = queue.receive::<NextState<S>>().last() {
+            if let Some(state) = world.resource_mut::<State<S>>() {
+                state.set(next.clone());
+            }
+        }
+    }
+}
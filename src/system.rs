@@ -1,9 +1,184 @@
+use std::any::type_name;
+use std::marker::PhantomData;
+
+use super::component::Component;
 use super::world::World;
-use super::event::{EventQueue, EventEmitter};
+use super::event::{Event, EventQueue, EventEmitter};
+use super::param::ParamFunction;
+
+/// Identifies a `System` registered with a `Simulation`. Ids are not reused, even after the
+/// `System` is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SystemId(pub(crate) usize);
 
 /// Trait that must be implemented by all systems in the `Simulation`.
 pub trait System {
+    /// Declare the components, resources and events that the `System` uses, so that a
+    /// `SimulationBuilder` can check that they have been registered before the `Simulation` runs.
+    fn requirements(&self, _requirements: &mut Requirements) {}
+
+    /// This method is called once, when the `System` is registered with the `Simulation`. Events
+    /// emitted here are received during the first update.
+    fn init(&mut self, _world: &mut World, _emitter: &mut EventEmitter) {}
+
+    /// Return `false` to skip `update` during this frame. `queue` contains the events that the
+    /// `System` has not yet received.
+    fn should_run(&mut self, _world: &World, _queue: &EventQueue, _dt: f32) -> bool {
+        true
+    }
+
     /// This method is called each frame, giving the `System` access to the `World`, `EventQueue`,
     /// and `EventEmitter`. `dt` is the time in milliseconds since the last update.
     fn update(&mut self, world: &mut World, queue: &EventQueue, emitter: &mut EventEmitter, dt: f32);
+
+    /// This method is called once, at the end of the frame in which the `Simulation` halts, or
+    /// when the `System` is removed or replaced. Once halted, the `Simulation` no longer updates
+    /// its systems.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// use trex::*;
+    ///
+    /// pub struct LogSystem(Rc<RefCell<Vec<&'static str>>>);
+    ///
+    /// impl System for LogSystem {
+    ///     fn init(&mut self, _world: &mut World, _emitter: &mut EventEmitter) {
+    ///         self.0.borrow_mut().push("init");
+    ///     }
+    ///
+    ///     fn update(&mut self, _world: &mut World, _queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+    ///         self.0.borrow_mut().push("update");
+    ///         emitter.emit(Halt::success());
+    ///     }
+    ///
+    ///     fn shutdown(&mut self, _world: &mut World) {
+    ///         self.0.borrow_mut().push("shutdown");
+    ///     }
+    /// }
+    ///
+    /// let log = Rc::new(RefCell::new(Vec::new()));
+    /// let mut simulation = Simulation::builder().build().unwrap();
+    /// simulation.register(LogSystem(log.clone()));
+    /// simulation.update(16.0);
+    /// simulation.update(16.0);
+    /// assert_eq!(*log.borrow(), vec!["init", "update", "shutdown"]);
+    /// ```
+    fn shutdown(&mut self, _world: &mut World) {}
+}
+
+type Check = fn(&World, &EventQueue, &EventEmitter) -> bool;
+
+/// The kind of value that a `System` requires.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Requirement {
+    /// A component class registered with the `World`.
+    Component,
+    /// A resource in the `World`.
+    Resource,
+    /// An event type registered with the `EventQueue` and the `EventEmitter`.
+    Event,
+}
+
+/// The components, resources and events that a `System` uses. See `System::requirements`.
+pub struct Requirements {
+    checks: Vec<(Requirement, &'static str, Check)>,
+}
+
+fn has_component<C: Component>(world: &World, _queue: &EventQueue, _emitter: &EventEmitter) -> bool {
+    world.is_registered::<C>()
+}
+
+fn has_resource<R: 'static>(world: &World, _queue: &EventQueue, _emitter: &EventEmitter) -> bool {
+    world.has_resource::<R>()
+}
+
+fn has_event<T: Event>(_world: &World, queue: &EventQueue, emitter: &EventEmitter) -> bool {
+    queue.is_registered::<T>() && emitter.is_registered::<T>()
+}
+
+impl Requirements {
+    /// Create an empty set of requirements.
+    pub fn new() -> Requirements {
+        Requirements {
+            checks: Vec::new(),
+        }
+    }
+
+    /// Require a component class to be registered.
+    pub fn component<C: 'static + Component>(&mut self) -> &mut Self {
+        self.checks.push((Requirement::Component, type_name::<C>(), has_component::<C>));
+        self
+    }
+
+    /// Require a resource to exist once every `System` has been initialized.
+    pub fn resource<R: 'static>(&mut self) -> &mut Self {
+        self.checks.push((Requirement::Resource, type_name::<R>(), has_resource::<R>));
+        self
+    }
+
+    /// Require an event type that the `System` emits or receives to be registered.
+    pub fn event<T: Event>(&mut self) -> &mut Self {
+        self.checks.push((Requirement::Event, type_name::<T>(), has_event::<T>));
+        self
+    }
+
+    /// The first requirement that is not met, if any.
+    pub(crate) fn unmet(&self, world: &World, queue: &EventQueue, emitter: &EventEmitter)
+        -> Option<(Requirement, &'static str)> {
+        self.checks.iter()
+            .find(|&&(_, _, check)| !check(world, queue, emitter))
+            .map(|&(requirement, name, _)| (requirement, name))
+    }
+}
+
+impl Default for Requirements {
+    fn default() -> Requirements {
+        Requirements::new()
+    }
+}
+
+/// A `System` that calls a closure, created by `Simulation::register_fn`.
+pub struct FnSystem<F> {
+    func: F,
+}
+
+impl<F> FnSystem<F> {
+    pub fn new(func: F) -> FnSystem<F> {
+        FnSystem {
+            func,
+        }
+    }
+}
+
+impl<F: FnMut(&mut World, &EventQueue, &mut EventEmitter, f32)> System for FnSystem<F> {
+    fn update(&mut self, world: &mut World, queue: &EventQueue, emitter: &mut EventEmitter, dt: f32) {
+        (self.func)(world, queue, emitter, dt);
+    }
+}
+
+/// A `System` that calls a function with the parameters it asks for, created by
+/// `Simulation::register_param_fn`.
+pub struct ParamSystem<F, P> {
+    func: F,
+    _params: PhantomData<fn() -> P>,
+}
+
+impl<F, P> ParamSystem<F, P> {
+    pub fn new(func: F) -> ParamSystem<F, P> {
+        ParamSystem {
+            func,
+            _params: PhantomData,
+        }
+    }
+}
+
+impl<P, F: ParamFunction<P>> System for ParamSystem<F, P> {
+    fn update(&mut self, world: &mut World, queue: &EventQueue, emitter: &mut EventEmitter, _dt: f32) {
+        // The parameters were checked for conflicts when the function was registered.
+        unsafe { self.func.run(world, queue, emitter) }
+    }
 }
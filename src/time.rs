@@ -1,6 +1,20 @@
 use std::time::Duration;
 
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 /// Helper function for calculating the time in milliseconds since the last update.
 pub fn calc_millis(dt: Duration) -> f32 {
     (dt.as_secs() as f32 * 1000.0) + (dt.subsec_nanos() as f32 / 1000000.0)
 }
+
+/// A resource that holds the timing of the current simulation step. Updated by the `Simulation`
+/// at the start of each step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Time {
+    /// The time in milliseconds since the last update.
+    pub delta: f32,
+    /// The total time in milliseconds that has been simulated, including this step.
+    pub elapsed: f32,
+}
@@ -1,12 +1,15 @@
-use std::mem::transmute;
+use std::any::{Any, TypeId};
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::fmt::{self, Debug};
 
 use vec_map::VecMap;
 use bit_set::BitSet;
 
 use super::id::{Id, IdPool};
-use super::component::{AnyComponentStore, InnerComponentStore};
-use super::family::FamilyMember;
+use super::component::{AnyComponentStore, Component, InnerComponentStore};
+use super::family::Family;
+use super::remap::{EntityMap, MapEntities};
 
 /// Used to filter the list of entities based on the components that are attached to them.
 pub struct ComponentFilter {
@@ -22,7 +25,7 @@ impl ComponentFilter {
     }
 
     /// Extend the filter to include the given component type.
-    pub fn with<C: FamilyMember>(mut self) -> Self {
+    pub fn with<C: Component>(mut self) -> Self {
         self.mask.insert(C::family());
         self
     }
@@ -33,16 +36,32 @@ impl ComponentFilter {
     }
 }
 
+impl Default for ComponentFilter {
+    fn default() -> ComponentFilter {
+        ComponentFilter::new()
+    }
+}
+
 /// Used to group components.
 pub type Entity = Id;
 
 /// Contains all entities and their components.
 pub struct World {
     masks: VecMap<BitSet>,
-    stores: VecMap<Box<AnyComponentStore>>,
+    stores: VecMap<Box<dyn AnyComponentStore>>,
     pool: IdPool,
     tags: HashMap<String, Entity>,
     tags_by_entity: VecMap<String>,
+    resources: HashMap<TypeId, Resource>,
+    // Incremented each time a resource may have changed.
+    changes: usize,
+    // The total number of entities returned by `filter`, used to profile systems.
+    filtered: Cell<usize>,
+}
+
+struct Resource {
+    value: Box<dyn Any>,
+    version: usize,
 }
 
 impl World {
@@ -54,15 +73,50 @@ impl World {
             pool: IdPool::new(),
             tags: HashMap::new(),
             tags_by_entity: VecMap::new(),
+            resources: HashMap::new(),
+            changes: 0,
+            filtered: Cell::new(0),
         }
     }
 
     /// Register a new component class.
-    pub fn register<C: 'static + FamilyMember>(&mut self) {
+    pub fn register<C: 'static + Component>(&mut self) {
         let store = InnerComponentStore::<C>::new();
         self.stores.insert(C::family(), Box::new(store));
     }
 
+    /// Allow a component class to be copied by `clone_entity` and `copy_entity_to`. Registers the
+    /// component class if it has not been registered yet.
+    pub fn register_cloneable<C: 'static + Component + Clone>(&mut self) {
+        self.register_if_absent::<C>();
+        self.get_store_mut::<C>().set_cloner(C::clone);
+    }
+
+    /// Allow the entities stored in a component class to be remapped by `merge` and `split`.
+    /// Registers the component class if it has not been registered yet.
+    pub fn register_mapped<C: 'static + Component + MapEntities>(&mut self) {
+        self.register_if_absent::<C>();
+        self.get_store_mut::<C>().set_mapper(C::map_entities);
+    }
+
+    /// Allow a component class to be formatted by `dump`. Registers the component class if it has
+    /// not been registered yet.
+    pub fn register_debug<C: 'static + Component + Debug>(&mut self) {
+        self.register_if_absent::<C>();
+        self.get_store_mut::<C>().set_debugger(C::fmt);
+    }
+
+    fn register_if_absent<C: 'static + Component>(&mut self) {
+        if !self.is_registered::<C>() {
+            self.register::<C>();
+        }
+    }
+
+    /// Returns `true` if the component class has been registered, otherwise `false`.
+    pub fn is_registered<C: Component>(&self) -> bool {
+        self.stores.contains_key(C::family())
+    }
+
     /// Returns `true` if the entity has been created and is not destroyed, otherwise `false`.
     ///
     /// # Examples
@@ -76,9 +130,24 @@ impl World {
         self.pool.is_reserved(entity)
     }
 
-    /// Create a new `Entity`.
+    /// Create a new `Entity`. The ids of destroyed entities are reused.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut world = trex::World::new();
+    /// let entity = world.create();
+    /// world.destroy(entity);
+    /// assert!(!world.exists(entity));
+    ///
+    /// let reused = world.create();
+    /// assert_eq!(reused, entity);
+    /// assert!(world.exists(reused));
+    /// assert_eq!(world.filter(&trex::ComponentFilter::new()), vec![reused]);
+    /// ```
     pub fn create(&mut self) -> Entity {
         let entity = self.pool.reserve();
+        span!(TRACE, "create", entity);
         self.accomodate_entity(entity);
         entity
     }
@@ -91,7 +160,8 @@ impl World {
         }
     }
 
-    /// Assign a tag to the `Entity` so that it can be retrieved later.
+    /// Assign a tag to the `Entity` so that it can be retrieved later. Replaces the existing tag of
+    /// the `Entity`, and removes the tag from any other `Entity` that has it.
     ///
     /// # Examples
     ///
@@ -100,9 +170,18 @@ impl World {
     /// let entity = world.create();
     /// world.tag(entity, "Example");
     /// assert_eq!(world.lookup("Example"), Some(entity));
+    ///
+    /// let other = world.create();
+    /// world.tag(other, "Example");
+    /// assert_eq!(world.lookup("Example"), Some(other));
+    /// assert_eq!(world.tag_of(entity), None);
     /// ```
     pub fn tag(&mut self, entity: Entity, tag: &str) {
         if self.exists(entity) {
+            self.untag(entity);
+            if let Some(previous) = self.lookup(tag) {
+                self.untag(previous);
+            }
             self.tags.insert(tag.to_owned(), entity);
             self.tags_by_entity.insert(entity, tag.to_owned());
         }
@@ -131,6 +210,11 @@ impl World {
         self.tags.get(&owned).cloned()
     }
 
+    /// Retreive the tag of an `Entity`, if it has one.
+    pub fn tag_of(&self, entity: Entity) -> Option<&str> {
+        self.tags_by_entity.get(entity).map(|tag| tag.as_str())
+    }
+
     /// Destroy an existing `Entity`. Also removes the tag and any attached components.
     ///
     /// # Examples
@@ -143,6 +227,7 @@ impl World {
     /// assert_eq!(world.lookup("Example"), None);
     /// ```
     pub fn destroy(&mut self, entity: Entity) {
+        span!(TRACE, "destroy", entity);
         if self.exists(entity) {
             self.pool.release(entity);
             self.remove_all_components(entity);
@@ -158,29 +243,339 @@ impl World {
         }
     }
 
+    /// Create a new `Entity` with a copy of each component of an existing `Entity`. Only
+    /// components registered with `register_cloneable` are copied, and the tag is not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// #[derive(Clone)]
+    /// pub struct Health(u32);
+    ///
+    /// components!(Health);
+    ///
+    /// fn main() {
+    ///     let mut world = trex::World::new();
+    ///     world.register_cloneable::<Health>();
+    ///     let entity = world.create();
+    ///     world.add(entity, Health(100));
+    ///
+    ///     let clone = world.clone_entity(entity);
+    ///     world.get_mut::<Health>(entity).unwrap().0 = 50;
+    ///     assert_eq!(world.get::<Health>(clone).unwrap().0, 100);
+    /// }
+    /// ```
+    pub fn clone_entity(&mut self, entity: Entity) -> Entity {
+        assert!(self.exists(entity));
+        let clone = self.create();
+        for family in self.families_of(entity) {
+            let cloned = self.stores.get_mut(family).unwrap().clone_component(entity, clone);
+            if cloned {
+                self.masks.get_mut(clone).unwrap().insert(family);
+            }
+        }
+        clone
+    }
+
+    /// Create a new `Entity` in another `World` with a copy of each component and the tag of an
+    /// existing `Entity`. Only components registered with `register_cloneable` in this `World`
+    /// and registered in the other `World` are copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// #[derive(Clone)]
+    /// pub struct Health(u32);
+    ///
+    /// pub struct Name(&'static str);
+    ///
+    /// components!(Health, Name);
+    ///
+    /// fn main() {
+    ///     let mut world = trex::World::new();
+    ///     world.register_cloneable::<Health>();
+    ///     world.register::<Name>();
+    ///     let entity = world.create();
+    ///     world.tag(entity, "Player");
+    ///     world.add(entity, Health(100));
+    ///     world.add(entity, Name("Hero"));
+    ///
+    ///     let mut other = trex::World::new();
+    ///     other.register::<Health>();
+    ///     other.register::<Name>();
+    ///     let copy = world.copy_entity_to(&mut other, entity);
+    ///     assert_eq!(other.get::<Health>(copy).unwrap().0, 100);
+    ///     assert!(!other.has::<Name>(copy));
+    ///     assert_eq!(other.lookup("Player"), Some(copy));
+    ///     assert_eq!(world.lookup("Player"), Some(entity));
+    /// }
+    /// ```
+    pub fn copy_entity_to(&self, other: &mut World, entity: Entity) -> Entity {
+        assert!(self.exists(entity));
+        let copy = other.create();
+        for family in self.families_of(entity) {
+            if let Some(other_store) = other.stores.get_mut(family) {
+                let store = self.stores.get(family).unwrap();
+                if store.copy_component(entity, &mut **other_store, copy) {
+                    other.masks.get_mut(copy).unwrap().insert(family);
+                }
+            }
+        }
+
+        if let Some(tag) = self.tag_of(entity) {
+            other.tag(copy, tag);
+        }
+        copy
+    }
+
+    /// Move every `Entity` from another `World` into this one, giving them new ids. Entities stored
+    /// in components registered with `register_mapped` in either `World` are remapped, and the tags
+    /// are kept, taking them from any entities of this `World` that have the same tags. Returns the
+    /// map from the entities of the other `World` to the moved entities, which lists any references
+    /// that could not be remapped in `EntityMap::dangling`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::{EntityMap, MapEntities, World};
+    ///
+    /// pub struct Door { pub leads_to: trex::Entity }
+    ///
+    /// impl MapEntities for Door {
+    ///     fn map_entities(&mut self, map: &mut EntityMap) {
+    ///         self.leads_to = map.remap(self.leads_to);
+    ///     }
+    /// }
+    ///
+    /// components!(Door);
+    ///
+    /// fn main() {
+    ///     // Registering the class with `register_mapped` in either `World` is enough.
+    ///     let mut world = World::new();
+    ///     world.register::<Door>();
+    ///     world.create();
+    ///
+    ///     let mut level = World::new();
+    ///     level.register_mapped::<Door>();
+    ///     let hall = level.create();
+    ///     let door = level.create();
+    ///     level.tag(hall, "Hall");
+    ///     level.add(door, Door { leads_to: hall });
+    ///
+    ///     let map = world.merge(level);
+    ///     let door = map.get(door).unwrap();
+    ///     assert_eq!(world.get::<Door>(door).unwrap().leads_to, world.lookup("Hall").unwrap());
+    ///     assert!(map.dangling().is_empty());
+    /// }
+    /// ```
+    ///
+    /// ```
+    /// let mut world = trex::World::new();
+    /// let old_hall = world.create();
+    /// world.tag(old_hall, "Hall");
+    ///
+    /// let mut level = trex::World::new();
+    /// let hall = level.create();
+    /// level.tag(hall, "Hall");
+    ///
+    /// let map = world.merge(level);
+    /// let hall = map.get(hall).unwrap();
+    /// world.destroy(old_hall);
+    /// assert_eq!(world.lookup("Hall"), Some(hall));
+    /// assert_eq!(world.tag_of(hall), Some("Hall"));
+    /// ```
+    pub fn merge(&mut self, mut other: World) -> EntityMap {
+        span!(TRACE, "merge_world");
+        let entities = other.pool.reserved().collect::<Vec<Entity>>();
+        other.transfer(self, &entities)
+    }
+
+    /// Move the given entities into a new `World` with the same component classes, giving them
+    /// new ids. Entities stored in components registered with `register_mapped` are remapped if
+    /// they were moved as well, and the tags are kept. Returns the new `World` and the map from the
+    /// given entities to the moved entities. References to entities that were not moved keep their
+    /// ids and are listed in `EntityMap::dangling`, so that the caller can remove the components
+    /// that hold them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::{EntityMap, MapEntities, World};
+    ///
+    /// pub struct Pair { pub a: trex::Entity, pub b: trex::Entity }
+    ///
+    /// impl MapEntities for Pair {
+    ///     fn map_entities(&mut self, map: &mut EntityMap) {
+    ///         self.a = map.remap(self.a);
+    ///         self.b = map.remap(self.b);
+    ///     }
+    /// }
+    ///
+    /// components!(Pair);
+    ///
+    /// fn main() {
+    ///     let mut world = World::new();
+    ///     world.register_mapped::<Pair>();
+    ///     let outside = world.create();
+    ///     let x = world.create();
+    ///     let y = world.create();
+    ///     world.add(x, Pair { a: outside, b: y });
+    ///
+    ///     let (other, map) = world.split(&[x, y]);
+    ///     let (x, y) = (map.get(x).unwrap(), map.get(y).unwrap());
+    ///     let pair = other.get::<Pair>(x).unwrap();
+    ///     assert_eq!((pair.a, pair.b), (outside, y));
+    ///     assert_eq!(map.dangling(), &[(x, outside)]);
+    ///     assert!(world.exists(outside));
+    /// }
+    /// ```
+    pub fn split(&mut self, entities: &[Entity]) -> (World, EntityMap) {
+        span!(TRACE, "split_world", count = entities.len());
+        let mut other = World::new();
+        let map = self.transfer(&mut other, entities);
+        (other, map)
+    }
+
+    fn transfer(&mut self, other: &mut World, entities: &[Entity]) -> EntityMap {
+        let mut map = EntityMap::new();
+        let mut moved = Vec::new();
+        for &entity in entities {
+            assert!(self.exists(entity));
+            if map.get(entity).is_none() {
+                let created = other.create();
+                map.insert(entity, created);
+                moved.push((entity, created));
+            }
+        }
+
+        for (family, store) in self.stores.iter() {
+            if !other.stores.contains_key(family) {
+                other.stores.insert(family, store.empty());
+            }
+        }
+
+        // Every `Entity` has been created, so components can be remapped as they are moved. The
+        // mapper of either `World` is used, so that a class registered with `register_mapped` in
+        // only one of them is still remapped.
+        for &(entity, created) in &moved {
+            for family in self.families_of(entity) {
+                let store = self.stores.get_mut(family).unwrap();
+                let other_store = other.stores.get_mut(family).unwrap();
+                let mapped = store.map_entities(entity, &mut map);
+                if store.move_component(entity, &mut **other_store, created) {
+                    other.masks.get_mut(created).unwrap().insert(family);
+                    if !mapped {
+                        other_store.map_entities(created, &mut map);
+                    }
+                }
+            }
+            map.attribute(created);
+            self.masks.get_mut(entity).unwrap().clear();
+
+            if let Some(tag) = self.tag_of(entity).map(|tag| tag.to_owned()) {
+                other.tag(created, &tag);
+            }
+            self.destroy(entity);
+        }
+
+        map
+    }
+
+    fn families_of(&self, entity: Entity) -> Vec<Family> {
+        self.masks.get(entity).unwrap().iter().collect()
+    }
+
     /// Returns a list of all `Entity`s with a given set of components.
     pub fn filter(&self, filter: &ComponentFilter) -> Vec<Entity> {
-        self.pool.reserved()
+        let entities = self.pool.reserved()
             .filter(|&entity| {
                 let mask = self.masks.get(entity).unwrap();
                 filter.matches(mask)
             })
-            .collect::<Vec<Entity>>()
+            .collect::<Vec<Entity>>();
+        self.filtered.set(self.filtered.get() + entities.len());
+        entities
+    }
+
+    /// Returns `true` if the `Entity` exists and has all of the components in the filter, otherwise
+    /// `false`.
+    pub fn matches(&self, entity: Entity, filter: &ComponentFilter) -> bool {
+        self.pool.exists(entity) && self.exists(entity) && filter.matches(self.masks.get(entity).unwrap())
+    }
+
+    /// List every `Entity` with its tag and components. Components registered with
+    /// `register_debug` are formatted with `Debug`, and others are shown by the name of their type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate trex;
+    ///
+    /// use trex::*;
+    ///
+    /// #[derive(Debug)]
+    /// pub struct Health(pub u32);
+    ///
+    /// pub struct Player;
+    ///
+    /// components!(Health, Player);
+    ///
+    /// fn main() {
+    ///     let mut world = World::new();
+    ///     world.register_debug::<Health>();
+    ///     world.register::<Player>();
+    ///
+    ///     let player = world.create();
+    ///     world.tag(player, "Player");
+    ///     world.add(player, Health(100));
+    ///     world.add(player, Player);
+    ///     let monster = world.create();
+    ///     world.add(monster, Health(20));
+    ///
+    ///     let dump = world.dump();
+    ///     assert!(dump.starts_with("Entity 0 \"Player\"\n    Health(100)\n"));
+    ///     assert!(dump.ends_with("::Player\nEntity 1\n    Health(20)\n"));
+    ///     assert_eq!(world.dump_matching(&ComponentFilter::new().with::<Player>()).lines().count(), 3);
+    /// }
+    /// ```
+    pub fn dump(&self) -> String {
+        self.dump_matching(&ComponentFilter::new())
+    }
+
+    /// List every `Entity` that has all of the components in the filter, in the same way as
+    /// `dump`.
+    pub fn dump_matching(&self, filter: &ComponentFilter) -> String {
+        Dump { world: self, filter }.to_string()
     }
 
     /// Attach a component to an `Entity`.
-    pub fn add<C: FamilyMember>(&mut self, entity: Entity, component: C) {
+    pub fn add<C: Component>(&mut self, entity: Entity, component: C) {
+        span!(TRACE, "add", entity, component = ::std::any::type_name::<C>());
         self.set_has_component::<C>(entity, true);
         self.get_store_mut::<C>().add(entity, component);
     }
 
     /// Remove a component from an `Entity`.
-    pub fn remove<C: FamilyMember>(&mut self, entity: Entity) {
+    pub fn remove<C: Component>(&mut self, entity: Entity) {
+        span!(TRACE, "remove", entity, component = ::std::any::type_name::<C>());
         self.set_has_component::<C>(entity, false);
-        self.get_store_mut::<C>().remove(entity);
+        self.get_store_mut::<C>().take(entity);
     }
 
-    fn set_has_component<C: FamilyMember>(&mut self, entity: Entity, has_component: bool) {
+    fn set_has_component<C: Component>(&mut self, entity: Entity, has_component: bool) {
         let mask = self.masks.get_mut(entity).unwrap();
         let family = C::family();
 
@@ -192,32 +587,163 @@ impl World {
     }
 
     /// Returns `true` if the `Entity` has the component, otherwise `false`.
-    pub fn has<C: FamilyMember>(&self, entity: Entity) -> bool {
+    pub fn has<C: Component>(&self, entity: Entity) -> bool {
         let mask = self.masks.get(entity).unwrap();
         mask.contains(C::family())
     }
 
     /// Get a component of an `Entity`.
-    pub fn get<C: FamilyMember>(&self, entity: Entity) -> Option<&C> {
+    pub fn get<C: Component>(&self, entity: Entity) -> Option<&C> {
         let store = self.get_store::<C>();
         store.get(entity)
     }
 
     /// Get a mutable component of an `Entity`.
-    pub fn get_mut<C: FamilyMember>(&mut self, entity: Entity) -> Option<&mut C> {
+    pub fn get_mut<C: Component>(&mut self, entity: Entity) -> Option<&mut C> {
         let store = self.get_store_mut::<C>();
         store.get_mut(entity)
     }
 
-    fn get_store<C: FamilyMember>(&self) -> &Box<InnerComponentStore<C>> {
+    /// Add a resource, which is a single value shared by every system rather than attached to an
+    /// `Entity`. Replaces any existing resource of the same type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// pub struct Score(u32);
+    ///
+    /// let mut world = trex::World::new();
+    /// world.add_resource(Score(0));
+    /// world.resource_mut::<Score>().unwrap().0 += 10;
+    /// assert_eq!(world.resource::<Score>().unwrap().0, 10);
+    /// ```
+    pub fn add_resource<R: 'static>(&mut self, resource: R) {
+        self.changes += 1;
+        let resource = Resource {
+            value: Box::new(resource),
+            version: self.changes,
+        };
+        self.resources.insert(TypeId::of::<R>(), resource);
+    }
+
+    /// Remove a resource, returning it if it existed.
+    pub fn remove_resource<R: 'static>(&mut self) -> Option<R> {
+        self.resources.remove(&TypeId::of::<R>())
+            .map(|resource| *resource.value.downcast::<R>().unwrap())
+    }
+
+    /// Returns `true` if the resource exists, otherwise `false`.
+    pub fn has_resource<R: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+
+    /// Get a resource.
+    pub fn resource<R: 'static>(&self) -> Option<&R> {
+        self.resources.get(&TypeId::of::<R>())
+            .and_then(|resource| resource.value.downcast_ref::<R>())
+    }
+
+    /// Get a mutable resource. The resource counts as changed, whether or not it is modified.
+    pub fn resource_mut<R: 'static>(&mut self) -> Option<&mut R> {
+        let changes = self.changes + 1;
+        match self.resources.get_mut(&TypeId::of::<R>()) {
+            Some(resource) => {
+                self.changes = changes;
+                resource.version = changes;
+                resource.value.downcast_mut::<R>()
+            },
+            None => None,
+        }
+    }
+
+    /// A number that increases each time the resource is added or borrowed mutably.
+    pub(crate) fn resource_version<R: 'static>(&self) -> Option<usize> {
+        self.resources.get(&TypeId::of::<R>()).map(|resource| resource.version)
+    }
+
+    /// The total number of entities that have been returned by `filter`.
+    pub(crate) fn filtered(&self) -> usize {
+        self.filtered.get()
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn ids(&self) -> &IdPool {
+        &self.pool
+    }
+
+    /// Remove every `Entity`, tag and component, then take entity ids from the given pool. The
+    /// entities reserved in the pool exist afterwards, without any components.
+    #[cfg(feature = "serde")]
+    pub(crate) fn reset(&mut self, pool: IdPool) {
+        for (_, store) in self.stores.iter_mut() {
+            store.clear();
+        }
+        self.masks.clear();
+        self.tags.clear();
+        self.tags_by_entity.clear();
+        self.pool = pool;
+
+        let entities = self.pool.reserved().collect::<Vec<Entity>>();
+        for entity in entities {
+            self.accomodate_entity(entity);
+        }
+    }
+
+    /// Get a pointer to a component of an `Entity` without borrowing the whole `World`, so that
+    /// references to other components of the same class stay valid.
+    ///
+    /// # Safety
+    ///
+    /// The `World` must be valid, and nothing else may borrow its stores during the call.
+    pub(crate) unsafe fn component_ptr<C: Component>(world: *mut World, entity: Entity) -> Option<*mut C> {
+        let store = (*world).stores.get_mut(C::family()).unwrap();
+        assert_eq!(store.family(), C::family());
+        let store = &mut **store as *mut dyn AnyComponentStore as *mut InnerComponentStore<C>;
+        (*store).get_ptr(entity)
+    }
+
+    fn get_store<C: Component>(&self) -> &InnerComponentStore<C> {
         let store = self.stores.get(C::family()).unwrap();
         assert_eq!(store.family(), C::family());
-        unsafe { transmute(store) }
+        unsafe { &*(&**store as *const dyn AnyComponentStore as *const InnerComponentStore<C>) }
     }
 
-    fn get_store_mut<C: FamilyMember>(&mut self) -> &mut Box<InnerComponentStore<C>> {
+    fn get_store_mut<C: Component>(&mut self) -> &mut InnerComponentStore<C> {
         let store = self.stores.get_mut(C::family()).unwrap();
         assert_eq!(store.family(), C::family());
-        unsafe { transmute(store) }
+        unsafe { &mut *(&mut **store as *mut dyn AnyComponentStore as *mut InnerComponentStore<C>) }
+    }
+}
+
+struct Dump<'a> {
+    world: &'a World,
+    filter: &'a ComponentFilter,
+}
+
+impl<'a> fmt::Display for Dump<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entity in self.world.pool.reserved() {
+            if !self.world.matches(entity, self.filter) {
+                continue;
+            }
+
+            match self.world.tag_of(entity) {
+                Some(tag) => writeln!(f, "Entity {} {:?}", entity, tag)?,
+                None => writeln!(f, "Entity {}", entity)?,
+            }
+
+            for family in self.world.families_of(entity) {
+                f.write_str("    ")?;
+                self.world.stores.get(family).unwrap().debug_component(entity, f)?;
+                f.write_str("\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
     }
 }